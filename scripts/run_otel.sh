@@ -2,6 +2,6 @@
 
 set -ex
 
-cargo build --features 'enable_otel'
+cargo build --no-default-features --features 'enable_otel'
 
 solana-test-validator --geyser-plugin-config ../config/sologger-geyser-plugin-config.json
\ No newline at end of file
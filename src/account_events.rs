@@ -0,0 +1,275 @@
+use agave_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoVersions;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use log::debug;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::accounts_selector::AccountsSelector;
+use crate::idl::IdlRegistry;
+
+/// An account update, normalized across the account info versions.
+/// Emitted as JSON on the `sologger_geyser_plugin::geyser_plugin_sologger::update_account` log target.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AccountEvent {
+    event: &'static str,
+    pubkey: String,
+    owner: String,
+    lamports: u64,
+    data_length: usize,
+    executable: bool,
+    rent_epoch: u64,
+    write_version: u64,
+    slot: u64,
+    /// True if the account was loaded from a snapshot when the validator started
+    is_startup: bool,
+    /// The signature of the transaction that updated the account, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    txn_signature: Option<String>,
+    /// The name of the account type in the IDL of the owner program, when the account data was decoded with it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account_type: Option<String>,
+    /// The account data decoded with the IDL of the owner program
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+    /// The base64 encoded account data, when the owner program has an IDL but the data could not be decoded with it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data_base64: Option<String>,
+}
+
+/// An account update queued for the worker threads, which decode its data with the IDL of the owner program
+pub(crate) struct AccountUpdate {
+    event: AccountEvent,
+    owner: Vec<u8>,
+    /// The account data, only copied when the owner program has an IDL
+    data: Option<Vec<u8>>,
+}
+
+impl AccountUpdate {
+    pub(crate) fn new(account: &ReplicaAccountInfoVersions, slot: u64, is_startup: bool, idls: &IdlRegistry) -> Self {
+        let (owner, data) = match account {
+            ReplicaAccountInfoVersions::V0_0_1(account) => (account.owner, account.data),
+            ReplicaAccountInfoVersions::V0_0_2(account) => (account.owner, account.data),
+            ReplicaAccountInfoVersions::V0_0_3(account) => (account.owner, account.data),
+        };
+        AccountUpdate {
+            event: AccountEvent::new(account, slot, is_startup),
+            owner: owner.to_vec(),
+            data: idls.contains(owner).then(|| data.to_vec()),
+        }
+    }
+
+    /// Returns the event for the account update, with the account data decoded if the owner program has an IDL
+    pub(crate) fn into_event(self, idls: &IdlRegistry) -> AccountEvent {
+        let Some(data) = self.data else { return self.event };
+        let event = self.event;
+        match idls.decode_account(&self.owner, &data) {
+            None => event,
+            Some(Ok(decoded)) => AccountEvent { account_type: Some(decoded.name), data: Some(decoded.value), ..event },
+            Some(Err(err)) => {
+                debug!("Unable to decode the data of account {}: {}", event.pubkey, err);
+                AccountEvent { data_base64: Some(STANDARD.encode(data)), ..event }
+            }
+        }
+    }
+}
+
+impl AccountEvent {
+    /// Returns the event for the account update, without its data. ReplicaAccountInfoVersions::V0_0_1 does not carry the transaction signature.
+    pub(crate) fn new(account: &ReplicaAccountInfoVersions, slot: u64, is_startup: bool) -> Self {
+        match account {
+            ReplicaAccountInfoVersions::V0_0_1(account) => AccountEvent {
+                event: "account",
+                pubkey: bs58::encode(account.pubkey).into_string(),
+                owner: bs58::encode(account.owner).into_string(),
+                lamports: account.lamports,
+                data_length: account.data.len(),
+                executable: account.executable,
+                rent_epoch: account.rent_epoch,
+                write_version: account.write_version,
+                slot,
+                is_startup,
+                txn_signature: None,
+                account_type: None,
+                data: None,
+                data_base64: None,
+            },
+            ReplicaAccountInfoVersions::V0_0_2(account) => AccountEvent {
+                event: "account",
+                pubkey: bs58::encode(account.pubkey).into_string(),
+                owner: bs58::encode(account.owner).into_string(),
+                lamports: account.lamports,
+                data_length: account.data.len(),
+                executable: account.executable,
+                rent_epoch: account.rent_epoch,
+                write_version: account.write_version,
+                slot,
+                is_startup,
+                txn_signature: account.txn_signature.map(|signature| signature.to_string()),
+                account_type: None,
+                data: None,
+                data_base64: None,
+            },
+            ReplicaAccountInfoVersions::V0_0_3(account) => AccountEvent {
+                event: "account",
+                pubkey: bs58::encode(account.pubkey).into_string(),
+                owner: bs58::encode(account.owner).into_string(),
+                lamports: account.lamports,
+                data_length: account.data.len(),
+                executable: account.executable,
+                rent_epoch: account.rent_epoch,
+                write_version: account.write_version,
+                slot,
+                is_startup,
+                txn_signature: account.txn.map(|txn| txn.signature().to_string()),
+                account_type: None,
+                data: None,
+                data_base64: None,
+            },
+        }
+    }
+}
+
+/// Returns true if the account pubkey or its owner is selected by the accountsSelector
+pub(crate) fn is_account_selected(account: &ReplicaAccountInfoVersions, accounts_selector: &AccountsSelector) -> bool {
+    let (pubkey, owner) = match account {
+        ReplicaAccountInfoVersions::V0_0_1(account) => (account.pubkey, account.owner),
+        ReplicaAccountInfoVersions::V0_0_2(account) => (account.pubkey, account.owner),
+        ReplicaAccountInfoVersions::V0_0_3(account) => (account.pubkey, account.owner),
+    };
+    accounts_selector.is_account_selected(pubkey, owner)
+}
+
+#[cfg(test)]
+mod tests {
+    use agave_geyser_plugin_interface::geyser_plugin_interface::{ReplicaAccountInfo, ReplicaAccountInfoV2, ReplicaAccountInfoV3, ReplicaAccountInfoVersions};
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::signature::{Keypair, Signature, Signer};
+    use solana_sdk::system_transaction;
+    use solana_sdk::transaction::SanitizedTransaction;
+    use crate::account_events::{is_account_selected, AccountEvent, AccountUpdate};
+    use crate::accounts_selector::AccountsSelector;
+    use crate::idl::IdlRegistry;
+    use crate::idl::tests::{test_counter_data, test_idl};
+
+    #[test]
+    fn test_account_event_json() {
+        let pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let signature = Signature::new_unique();
+        let account = ReplicaAccountInfoV2 {
+            pubkey: pubkey.as_ref(),
+            lamports: 1000,
+            owner: owner.as_ref(),
+            executable: false,
+            rent_epoch: 5,
+            data: &[1, 2, 3],
+            write_version: 7,
+            txn_signature: Some(&signature),
+        };
+        let account = ReplicaAccountInfoVersions::V0_0_2(&account);
+
+        assert!(is_account_selected(&account, &AccountsSelector::new(&[], &[owner.to_string()])));
+        assert!(!is_account_selected(&account, &AccountsSelector::default()));
+
+        let json = serde_json::to_value(AccountEvent::new(&account, 10, false)).unwrap();
+        assert_eq!(json["event"], "account");
+        assert_eq!(json["pubkey"], pubkey.to_string());
+        assert_eq!(json["owner"], owner.to_string());
+        assert_eq!(json["lamports"], 1000);
+        assert_eq!(json["dataLength"], 3);
+        assert_eq!(json["executable"], false);
+        assert_eq!(json["rentEpoch"], 5);
+        assert_eq!(json["writeVersion"], 7);
+        assert_eq!(json["slot"], 10);
+        assert_eq!(json["isStartup"], false);
+        assert_eq!(json["txnSignature"], signature.to_string());
+        assert!(json.get("data").is_none());
+        assert!(json.get("dataBase64").is_none());
+    }
+
+    #[test]
+    fn test_account_event_idl() {
+        let pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mut idls = IdlRegistry::default();
+        idls.insert(owner, test_idl());
+
+        let data = test_counter_data(&authority);
+        let account = ReplicaAccountInfoV2 {
+            pubkey: pubkey.as_ref(),
+            lamports: 1000,
+            owner: owner.as_ref(),
+            executable: false,
+            rent_epoch: 5,
+            data: &data,
+            write_version: 7,
+            txn_signature: None,
+        };
+        let json = serde_json::to_value(AccountUpdate::new(&ReplicaAccountInfoVersions::V0_0_2(&account), 10, false, &idls).into_event(&idls)).unwrap();
+        assert_eq!(json["accountType"], "Counter");
+        assert_eq!(json["data"]["authority"], authority.to_string());
+        assert_eq!(json["data"]["count"], 42);
+        assert!(json.get("dataBase64").is_none());
+
+        let account = ReplicaAccountInfoV2 { data: &[9, 9, 9], ..account };
+        let json = serde_json::to_value(AccountUpdate::new(&ReplicaAccountInfoVersions::V0_0_2(&account), 10, false, &idls).into_event(&idls)).unwrap();
+        assert!(json.get("accountType").is_none());
+        assert!(json.get("data").is_none());
+        assert_eq!(json["dataBase64"], "CQkJ");
+    }
+
+    #[test]
+    fn test_account_event_v0_0_1() {
+        let pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let account = ReplicaAccountInfo {
+            pubkey: pubkey.as_ref(),
+            lamports: 1000,
+            owner: owner.as_ref(),
+            executable: true,
+            rent_epoch: 5,
+            data: &[1, 2, 3, 4],
+            write_version: 7,
+        };
+        let account = ReplicaAccountInfoVersions::V0_0_1(&account);
+
+        assert!(is_account_selected(&account, &AccountsSelector::new(&[pubkey.to_string()], &[])));
+
+        let json = serde_json::to_value(AccountEvent::new(&account, 10, true)).unwrap();
+        assert_eq!(json["pubkey"], pubkey.to_string());
+        assert_eq!(json["owner"], owner.to_string());
+        assert_eq!(json["dataLength"], 4);
+        assert_eq!(json["executable"], true);
+        assert_eq!(json["isStartup"], true);
+        assert!(json.get("txnSignature").is_none());
+    }
+
+    #[test]
+    fn test_account_event_v0_0_3() {
+        let pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let keypair = Keypair::new();
+        let transaction = system_transaction::transfer(&keypair, &keypair.pubkey(), 42, Hash::default());
+        let transaction = SanitizedTransaction::from_transaction_for_tests(transaction);
+        let account = ReplicaAccountInfoV3 {
+            pubkey: pubkey.as_ref(),
+            lamports: 1000,
+            owner: owner.as_ref(),
+            executable: false,
+            rent_epoch: 5,
+            data: &[],
+            write_version: 7,
+            txn: Some(&transaction),
+        };
+        let account = ReplicaAccountInfoVersions::V0_0_3(&account);
+
+        let json = serde_json::to_value(AccountEvent::new(&account, 10, false)).unwrap();
+        assert_eq!(json["pubkey"], pubkey.to_string());
+        assert_eq!(json["dataLength"], 0);
+        assert_eq!(json["txnSignature"], transaction.signature().to_string());
+    }
+}
@@ -0,0 +1,56 @@
+use sologger_log_context::programs_selector::ProgramsSelector;
+
+/// Selects the account updates to emit, by account pubkey or by owner program. Both lists follow the programsSelector semantics:
+/// base58 encoded pubkeys, with "*" selecting all accounts.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct AccountsSelector {
+    pub accounts: ProgramsSelector,
+    pub owners: ProgramsSelector,
+}
+
+impl AccountsSelector {
+    pub fn new(accounts: &[String], owners: &[String]) -> Self {
+        AccountsSelector {
+            accounts: ProgramsSelector::new(accounts),
+            owners: ProgramsSelector::new(owners),
+        }
+    }
+
+    /// Returns true if the account pubkey or its owner is selected
+    pub fn is_account_selected(&self, pubkey: &[u8], owner: &[u8]) -> bool {
+        self.accounts.is_program_selected(pubkey) || self.owners.is_program_selected(owner)
+    }
+
+    /// Returns true if any account or owner is selected
+    pub fn is_enabled(&self) -> bool {
+        self.accounts.is_enabled() || self.owners.is_enabled()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::pubkey::Pubkey;
+    use crate::accounts_selector::AccountsSelector;
+
+    #[test]
+    fn test_accounts_selector() {
+        let account = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+
+        let selector = AccountsSelector::default();
+        assert!(!selector.is_enabled());
+        assert!(!selector.is_account_selected(account.as_ref(), owner.as_ref()));
+
+        let selector = AccountsSelector::new(&[account.to_string()], &[]);
+        assert!(selector.is_account_selected(account.as_ref(), other.as_ref()));
+        assert!(!selector.is_account_selected(other.as_ref(), owner.as_ref()));
+
+        let selector = AccountsSelector::new(&[], &[owner.to_string()]);
+        assert!(selector.is_account_selected(other.as_ref(), owner.as_ref()));
+        assert!(!selector.is_account_selected(account.as_ref(), other.as_ref()));
+
+        let selector = AccountsSelector::new(&["*".to_string()], &[]);
+        assert!(selector.is_account_selected(other.as_ref(), other.as_ref()));
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use agave_geyser_plugin_interface::geyser_plugin_interface::ReplicaBlockInfoVersions;
+use log::info;
+use serde::Serialize;
+use solana_sdk::clock::UnixTimestamp;
+use solana_transaction_status::Reward;
+use sologger_log_context::sologger_log_context::LogContext;
+
+use crate::log_processor::compute_units_consumed;
+
+/// The number of slots below the latest block to keep program summaries for, for slots that never get block metadata
+const MAX_TRACKED_SLOTS: u64 = 512;
+
+/// The block metadata of a slot, normalized across the block info versions.
+/// Emitted as JSON on the `sologger_geyser_plugin::geyser_plugin_sologger::notify_block_metadata` log target.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BlockEvent {
+    event: &'static str,
+    pub(crate) slot: u64,
+    blockhash: String,
+    parent_slot: Option<u64>,
+    parent_blockhash: Option<String>,
+    block_height: Option<u64>,
+    pub(crate) block_time: Option<UnixTimestamp>,
+    executed_transaction_count: Option<u64>,
+    entry_count: Option<u64>,
+    rewards: RewardsSummary,
+    /// The invocations of the selected programs in this block, keyed by program ID. Only set when blockProgramRollup is enabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    programs: Option<BTreeMap<String, ProgramSummary>>,
+}
+
+impl From<&ReplicaBlockInfoVersions<'_>> for BlockEvent {
+    fn from(blockinfo: &ReplicaBlockInfoVersions<'_>) -> Self {
+        let event = BlockEvent {
+            event: "block",
+            slot: 0,
+            blockhash: String::new(),
+            parent_slot: None,
+            parent_blockhash: None,
+            block_height: None,
+            block_time: None,
+            executed_transaction_count: None,
+            entry_count: None,
+            rewards: RewardsSummary::default(),
+            programs: None,
+        };
+        match blockinfo {
+            ReplicaBlockInfoVersions::V0_0_1(blockinfo) => BlockEvent {
+                slot: blockinfo.slot,
+                blockhash: blockinfo.blockhash.to_string(),
+                block_height: blockinfo.block_height,
+                block_time: blockinfo.block_time,
+                rewards: RewardsSummary::new(blockinfo.rewards, None),
+                ..event
+            },
+            ReplicaBlockInfoVersions::V0_0_2(blockinfo) => BlockEvent {
+                slot: blockinfo.slot,
+                blockhash: blockinfo.blockhash.to_string(),
+                parent_slot: Some(blockinfo.parent_slot),
+                parent_blockhash: Some(blockinfo.parent_blockhash.to_string()),
+                block_height: blockinfo.block_height,
+                block_time: blockinfo.block_time,
+                executed_transaction_count: Some(blockinfo.executed_transaction_count),
+                rewards: RewardsSummary::new(blockinfo.rewards, None),
+                ..event
+            },
+            ReplicaBlockInfoVersions::V0_0_3(blockinfo) => BlockEvent {
+                slot: blockinfo.slot,
+                blockhash: blockinfo.blockhash.to_string(),
+                parent_slot: Some(blockinfo.parent_slot),
+                parent_blockhash: Some(blockinfo.parent_blockhash.to_string()),
+                block_height: blockinfo.block_height,
+                block_time: blockinfo.block_time,
+                executed_transaction_count: Some(blockinfo.executed_transaction_count),
+                entry_count: Some(blockinfo.entry_count),
+                rewards: RewardsSummary::new(blockinfo.rewards, None),
+                ..event
+            },
+            ReplicaBlockInfoVersions::V0_0_4(blockinfo) => BlockEvent {
+                slot: blockinfo.slot,
+                blockhash: blockinfo.blockhash.to_string(),
+                parent_slot: Some(blockinfo.parent_slot),
+                parent_blockhash: Some(blockinfo.parent_blockhash.to_string()),
+                block_height: blockinfo.block_height,
+                block_time: blockinfo.block_time,
+                executed_transaction_count: Some(blockinfo.executed_transaction_count),
+                entry_count: Some(blockinfo.entry_count),
+                rewards: RewardsSummary::new(&blockinfo.rewards.rewards, blockinfo.rewards.num_partitions),
+                ..event
+            },
+        }
+    }
+}
+
+/// Logs the block event
+pub(crate) fn emit_block_event(event: &BlockEvent) {
+    info!(target: "sologger_geyser_plugin::geyser_plugin_sologger::notify_block_metadata", "{}", serde_json::to_string(event).unwrap_or_default());
+}
+
+/// The number and total lamports of the rewards paid in a block
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RewardsSummary {
+    count: usize,
+    lamports: i64,
+    /// The total lamports per reward type: fee, rent, staking or voting
+    lamports_by_type: BTreeMap<String, i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_partitions: Option<u64>,
+}
+
+impl RewardsSummary {
+    fn new(rewards: &[Reward], num_partitions: Option<u64>) -> Self {
+        let mut lamports_by_type = BTreeMap::new();
+        for reward in rewards {
+            if let Some(reward_type) = reward.reward_type {
+                *lamports_by_type.entry(reward_type.to_string()).or_default() += reward.lamports;
+            }
+        }
+        RewardsSummary {
+            count: rewards.len(),
+            lamports: rewards.iter().map(|reward| reward.lamports).sum(),
+            lamports_by_type,
+            num_partitions,
+        }
+    }
+}
+
+/// The invocations of a selected program in a block
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ProgramSummary {
+    invocations: u64,
+    errors: u64,
+    compute_units: u64,
+}
+
+/// Summarizes the selected program invocations per block. The block event of a slot is held back until
+/// the logs of all the slot's transactions have been processed, or discarded, and then emitted with the summary.
+pub(crate) struct BlockRollups {
+    slots: Mutex<HashMap<u64, SlotRollup>>,
+}
+
+#[derive(Default)]
+struct SlotRollup {
+    pending_tasks: usize,
+    programs: BTreeMap<String, ProgramSummary>,
+    block: Option<BlockEvent>,
+}
+
+impl BlockRollups {
+    pub(crate) fn new() -> Self {
+        BlockRollups { slots: Mutex::new(HashMap::new()) }
+    }
+
+    /// Tracks a task of the slot. The slot's block event is held back until the returned guard is dropped.
+    pub(crate) fn track(self: &Arc<Self>, slot: u64) -> RollupGuard {
+        self.slots.lock().unwrap().entry(slot).or_default().pending_tasks += 1;
+        RollupGuard { rollups: Arc::clone(self), slot }
+    }
+
+    /// Adds the block event of the slot. It is emitted once all tracked tasks of the slot are done.
+    pub(crate) fn add_block(&self, event: BlockEvent) {
+        let slot = event.slot;
+        let mut slots = self.slots.lock().unwrap();
+        let oldest_tracked = slot.saturating_sub(MAX_TRACKED_SLOTS);
+        slots.retain(|slot, _| *slot >= oldest_tracked);
+        slots.entry(slot).or_default().block = Some(event);
+        Self::emit_if_done(&mut slots, slot);
+    }
+
+    fn record(&self, slot: u64, log_contexts: &[LogContext]) {
+        let mut slots = self.slots.lock().unwrap();
+        let Some(rollup) = slots.get_mut(&slot) else {
+            return;
+        };
+        for log_context in log_contexts {
+            let summary = rollup.programs.entry(log_context.program_id.clone()).or_default();
+            summary.invocations += 1;
+            if log_context.has_errors() {
+                summary.errors += 1;
+            }
+            summary.compute_units += compute_units_consumed(log_context).unwrap_or_default();
+        }
+    }
+
+    fn task_done(&self, slot: u64) {
+        let mut slots = self.slots.lock().unwrap();
+        if let Some(rollup) = slots.get_mut(&slot) {
+            rollup.pending_tasks = rollup.pending_tasks.saturating_sub(1);
+            Self::emit_if_done(&mut slots, slot);
+        }
+    }
+
+    /// Returns the number of tracked tasks of the slot that are not done yet, or None if the slot is not tracked
+    #[cfg(test)]
+    pub(crate) fn pending_tasks(&self, slot: u64) -> Option<usize> {
+        self.slots.lock().unwrap().get(&slot).map(|rollup| rollup.pending_tasks)
+    }
+
+    fn emit_if_done(slots: &mut HashMap<u64, SlotRollup>, slot: u64) {
+        let done = slots.get(&slot).is_some_and(|rollup| rollup.pending_tasks == 0 && rollup.block.is_some());
+        if let Some(rollup) = done.then(|| slots.remove(&slot)).flatten() {
+            if let Some(mut block) = rollup.block {
+                block.programs = Some(rollup.programs);
+                emit_block_event(&block);
+            }
+        }
+    }
+}
+
+impl Default for BlockRollups {
+    fn default() -> Self {
+        BlockRollups::new()
+    }
+}
+
+/// Marks a task as pending in the summary of its slot. Dropping the guard marks the task as done,
+/// whether it was processed or discarded.
+pub(crate) struct RollupGuard {
+    rollups: Arc<BlockRollups>,
+    slot: u64,
+}
+
+impl RollupGuard {
+    /// Adds the program invocations of the task to the summary of its slot
+    pub(crate) fn record(&self, log_contexts: &[LogContext]) {
+        self.rollups.record(self.slot, log_contexts);
+    }
+}
+
+impl Drop for RollupGuard {
+    fn drop(&mut self) {
+        self.rollups.task_done(self.slot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use agave_geyser_plugin_interface::geyser_plugin_interface::{ReplicaBlockInfo, ReplicaBlockInfoV4, ReplicaBlockInfoVersions};
+    use solana_sdk::reward_type::RewardType;
+    use solana_transaction_status::{Reward, RewardsAndNumPartitions};
+    use crate::block_events::{BlockEvent, BlockRollups};
+    use crate::log_processor::tests::log_context;
+
+    fn reward(lamports: i64, reward_type: RewardType) -> Reward {
+        Reward {
+            pubkey: "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin".to_string(),
+            lamports,
+            post_balance: 0,
+            reward_type: Some(reward_type),
+            commission: None,
+        }
+    }
+
+    #[test]
+    fn test_block_event_json() {
+        let rewards = RewardsAndNumPartitions {
+            rewards: vec![reward(5000, RewardType::Fee), reward(2500, RewardType::Fee), reward(100, RewardType::Voting)],
+            num_partitions: Some(4),
+        };
+        let blockinfo = ReplicaBlockInfoV4 {
+            parent_slot: 1,
+            parent_blockhash: "parent",
+            slot: 2,
+            blockhash: "hash",
+            rewards: &rewards,
+            block_time: Some(1700000000),
+            block_height: Some(3),
+            executed_transaction_count: 10,
+            entry_count: 5,
+        };
+        let json = serde_json::to_value(BlockEvent::from(&ReplicaBlockInfoVersions::V0_0_4(&blockinfo))).unwrap();
+        assert_eq!(json["event"], "block");
+        assert_eq!(json["slot"], 2);
+        assert_eq!(json["blockhash"], "hash");
+        assert_eq!(json["parentSlot"], 1);
+        assert_eq!(json["parentBlockhash"], "parent");
+        assert_eq!(json["blockHeight"], 3);
+        assert_eq!(json["blockTime"], 1700000000);
+        assert_eq!(json["executedTransactionCount"], 10);
+        assert_eq!(json["entryCount"], 5);
+        assert_eq!(json["rewards"]["count"], 3);
+        assert_eq!(json["rewards"]["lamports"], 7600);
+        assert_eq!(json["rewards"]["lamportsByType"]["fee"], 7500);
+        assert_eq!(json["rewards"]["lamportsByType"]["voting"], 100);
+        assert_eq!(json["rewards"]["numPartitions"], 4);
+        assert!(json.get("programs").is_none());
+    }
+
+    #[test]
+    fn test_block_event_v1() {
+        let blockinfo = ReplicaBlockInfo {
+            slot: 2,
+            blockhash: "hash",
+            rewards: &[],
+            block_time: None,
+            block_height: None,
+        };
+        let json = serde_json::to_value(BlockEvent::from(&ReplicaBlockInfoVersions::V0_0_1(&blockinfo))).unwrap();
+        assert_eq!(json["slot"], 2);
+        assert!(json["parentSlot"].is_null());
+        assert!(json["entryCount"].is_null());
+        assert_eq!(json["rewards"]["count"], 0);
+    }
+
+    #[test]
+    fn test_rollup() {
+        let rollups = Arc::new(BlockRollups::new());
+        let blockinfo = ReplicaBlockInfo { slot: 2, blockhash: "hash", rewards: &[], block_time: None, block_height: None };
+        let block = BlockEvent::from(&ReplicaBlockInfoVersions::V0_0_1(&blockinfo));
+
+        let processed = rollups.track(2);
+        let discarded = rollups.track(2);
+        rollups.add_block(block);
+
+        let mut succeeded = log_context("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", 0, 2);
+        succeeded.raw_logs.push("Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA consumed 1200 of 200000 compute units".to_string());
+        let mut failed = log_context("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", 1, 2);
+        failed.errors.push("custom program error: 0x1".to_string());
+        processed.record(&[succeeded, failed]);
+        drop(processed);
+
+        {
+            let slots = rollups.slots.lock().unwrap();
+            let summary = &slots.get(&2).unwrap().programs["TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"];
+            assert_eq!(summary.invocations, 2);
+            assert_eq!(summary.errors, 1);
+            assert_eq!(summary.compute_units, 1200);
+        }
+
+        // The block event is emitted once the last task of the slot is done
+        drop(discarded);
+        assert!(rollups.slots.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use solana_sdk::clock::UnixTimestamp;
+
+/// The number of slots to keep block times for. Transactions are notified before the block metadata of their slot,
+/// so only recent slots are ever looked up.
+const MAX_CACHED_SLOTS: u64 = 512;
+
+/// Caches the block time of recent slots, as received from the block metadata notifications.
+/// Tasks for slots without a block time yet are held back until the block metadata arrives or the timeout passes.
+pub(crate) struct BlockTimeCache<T> {
+    inner: Mutex<Inner<T>>,
+}
+
+struct Inner<T> {
+    block_times: BTreeMap<u64, Option<UnixTimestamp>>,
+    pending: BTreeMap<u64, PendingSlot<T>>,
+}
+
+struct PendingSlot<T> {
+    deadline: Instant,
+    tasks: Vec<T>,
+}
+
+impl<T> BlockTimeCache<T> {
+    pub(crate) fn new() -> Self {
+        BlockTimeCache {
+            inner: Mutex::new(Inner {
+                block_times: BTreeMap::new(),
+                pending: BTreeMap::new(),
+            }),
+        }
+    }
+
+    /// Returns the task with the block time of its slot if the block metadata has been received, or if `timeout` is zero.
+    /// Otherwise the task is held back until [BlockTimeCache::insert] is called for the slot or `timeout` has passed.
+    pub(crate) fn resolve(&self, slot: u64, task: T, timeout: Duration) -> Option<(T, Option<UnixTimestamp>)> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(block_time) = inner.block_times.get(&slot) {
+            return Some((task, *block_time));
+        }
+        if timeout.is_zero() {
+            return Some((task, None));
+        }
+        inner.pending
+            .entry(slot)
+            .or_insert_with(|| PendingSlot { deadline: Instant::now() + timeout, tasks: Vec::new() })
+            .tasks
+            .push(task);
+        None
+    }
+
+    /// Records the block time of the slot and returns the tasks that were waiting for it
+    pub(crate) fn insert(&self, slot: u64, block_time: Option<UnixTimestamp>) -> Vec<T> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.block_times.insert(slot, block_time);
+        while inner.block_times.len() as u64 > MAX_CACHED_SLOTS {
+            inner.block_times.pop_first();
+        }
+        inner.pending.remove(&slot).map(|pending| pending.tasks).unwrap_or_default()
+    }
+
+    /// Returns the tasks that have waited longer than their timeout for the block metadata
+    pub(crate) fn take_expired(&self, now: Instant) -> Vec<T> {
+        let mut inner = self.inner.lock().unwrap();
+        let expired: Vec<u64> = inner.pending
+            .iter()
+            .filter(|(_, pending)| pending.deadline <= now)
+            .map(|(slot, _)| *slot)
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|slot| inner.pending.remove(&slot))
+            .flat_map(|pending| pending.tasks)
+            .collect()
+    }
+
+    /// Returns the number of tasks waiting for block metadata
+    #[cfg(test)]
+    pub(crate) fn pending_len(&self) -> usize {
+        self.inner.lock().unwrap().pending.values().map(|pending| pending.tasks.len()).sum()
+    }
+
+    /// Returns all tasks still waiting for block metadata
+    pub(crate) fn take_all(&self) -> Vec<T> {
+        let mut inner = self.inner.lock().unwrap();
+        std::mem::take(&mut inner.pending)
+            .into_values()
+            .flat_map(|pending| pending.tasks)
+            .collect()
+    }
+}
+
+impl<T> Default for BlockTimeCache<T> {
+    fn default() -> Self {
+        BlockTimeCache::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use crate::block_time_cache::{BlockTimeCache, MAX_CACHED_SLOTS};
+
+    #[test]
+    fn test_resolve_after_insert() {
+        let cache = BlockTimeCache::new();
+        assert!(cache.insert(1, Some(1700000000)).is_empty());
+        assert_eq!(cache.resolve(1, "a", Duration::from_secs(1)), Some(("a", Some(1700000000))));
+    }
+
+    #[test]
+    fn test_resolve_deferred() {
+        let cache = BlockTimeCache::new();
+        assert_eq!(cache.resolve(1, "a", Duration::from_secs(1)), None);
+        assert_eq!(cache.resolve(1, "b", Duration::from_secs(1)), None);
+        assert_eq!(cache.resolve(2, "c", Duration::from_secs(1)), None);
+        assert_eq!(cache.insert(1, Some(1700000000)), vec!["a", "b"]);
+        assert_eq!(cache.take_all(), vec!["c"]);
+    }
+
+    #[test]
+    fn test_resolve_without_timeout() {
+        let cache = BlockTimeCache::new();
+        assert_eq!(cache.resolve(1, "a", Duration::ZERO), Some(("a", None)));
+    }
+
+    #[test]
+    fn test_take_expired() {
+        let cache = BlockTimeCache::new();
+        assert_eq!(cache.resolve(1, "a", Duration::from_millis(10)), None);
+        assert_eq!(cache.resolve(2, "b", Duration::from_secs(60)), None);
+        assert!(cache.take_expired(Instant::now()).is_empty());
+        assert_eq!(cache.take_expired(Instant::now() + Duration::from_secs(1)), vec!["a"]);
+        assert_eq!(cache.insert(2, None), vec!["b"]);
+    }
+
+    #[test]
+    fn test_max_cached_slots() {
+        let cache: BlockTimeCache<&str> = BlockTimeCache::new();
+        for slot in 0..MAX_CACHED_SLOTS + 1 {
+            cache.insert(slot, Some(slot as i64));
+        }
+        assert_eq!(cache.resolve(0, "a", Duration::ZERO), Some(("a", None)));
+        assert_eq!(cache.resolve(1, "b", Duration::ZERO), Some(("b", Some(1))));
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use agave_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+
+/// The number of slots below the latest root to keep track of, for slots notified out of order
+const MAX_TRACKED_SLOTS: u64 = 512;
+
+/// The commitment a slot must reach before the logs of its transactions are emitted
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub(crate) enum Commitment {
+    /// Emit logs as soon as the transaction is processed, including transactions on forks that are later abandoned
+    #[default]
+    Processed,
+    /// Emit logs once the slot has been voted on by a supermajority of the cluster
+    Confirmed,
+    /// Emit logs once the slot is rooted, i.e. finalized
+    Rooted,
+}
+
+impl FromStr for Commitment {
+    type Err = ();
+
+    /// Parses a commitment level, accepting the RPC names and their deprecated aliases
+    fn from_str(commitment_level: &str) -> Result<Self, Self::Err> {
+        match commitment_level {
+            "processed" | "recent" => Ok(Commitment::Processed),
+            "confirmed" | "single" | "singleGossip" => Ok(Commitment::Confirmed),
+            "rooted" | "finalized" | "root" | "max" => Ok(Commitment::Rooted),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<&SlotStatus> for Commitment {
+    fn from(status: &SlotStatus) -> Self {
+        match status {
+            SlotStatus::Processed => Commitment::Processed,
+            SlotStatus::Confirmed => Commitment::Confirmed,
+            SlotStatus::Rooted => Commitment::Rooted,
+        }
+    }
+}
+
+/// The tasks released by a slot status update
+pub(crate) struct Released<T> {
+    /// Tasks whose slot reached the configured commitment
+    pub(crate) committed: Vec<T>,
+    /// Tasks whose slot was abandoned: it is below the new root and the rooted chain is known to skip over it,
+    /// or it fell out of the tracked slots without reaching the commitment
+    pub(crate) orphaned: Vec<T>,
+}
+
+/// Holds tasks until their slot reaches the configured commitment, using the slot status notifications.
+/// A slot reaching a commitment means all of its ancestors did too, so the parent of each slot is tracked.
+pub(crate) struct CommitmentBuffer<T> {
+    inner: Mutex<Inner<T>>,
+}
+
+struct Inner<T> {
+    parents: BTreeMap<u64, u64>,
+    /// The highest commitment reached by each slot above processed, either notified or implied by a descendant
+    statuses: BTreeMap<u64, Commitment>,
+    pending: BTreeMap<u64, Vec<T>>,
+}
+
+impl<T> Inner<T> {
+    fn reached(&self, slot: u64, commitment: Commitment) -> bool {
+        commitment == Commitment::Processed || self.statuses.get(&slot).is_some_and(|status| *status >= commitment)
+    }
+
+    /// Returns true if a rooted slot has a known parent below `slot`, so `slot` is not on the rooted chain.
+    /// Slots whose parent link was not notified are not known to be abandoned, since they may still be ancestors of the root.
+    fn is_abandoned(&self, slot: u64) -> bool {
+        !self.reached(slot, Commitment::Rooted)
+            && self.parents
+                .range(slot + 1..)
+                .any(|(child, parent)| *parent < slot && self.reached(*child, Commitment::Rooted))
+    }
+}
+
+impl<T> CommitmentBuffer<T> {
+    pub(crate) fn new() -> Self {
+        CommitmentBuffer {
+            inner: Mutex::new(Inner {
+                parents: BTreeMap::new(),
+                statuses: BTreeMap::new(),
+                pending: BTreeMap::new(),
+            }),
+        }
+    }
+
+    /// Returns the task if its slot already reached `commitment`. Otherwise the task is held until [CommitmentBuffer::update] releases it.
+    pub(crate) fn hold(&self, slot: u64, task: T, commitment: Commitment) -> Option<T> {
+        if commitment == Commitment::Processed {
+            return Some(task);
+        }
+        let mut inner = self.inner.lock().unwrap();
+        if inner.reached(slot, commitment) {
+            return Some(task);
+        }
+        inner.pending.entry(slot).or_default().push(task);
+        None
+    }
+
+    /// Records the new status of the slot. Returns the held tasks of the slot and its ancestors if `status` reaches `commitment`,
+    /// and when the slot is rooted, the held tasks of the slots it abandoned.
+    pub(crate) fn update(&self, slot: u64, parent: Option<u64>, status: Commitment, commitment: Commitment) -> Released<T> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(parent) = parent {
+            inner.parents.insert(slot, parent);
+        }
+
+        let mut released = Released { committed: Vec::new(), orphaned: Vec::new() };
+        if status != Commitment::Processed {
+            let mut ancestor = Some(slot);
+            while let Some(current) = ancestor {
+                // The ancestors of a slot that already reached the status have too, unless its parent is only known now
+                let reached = inner.reached(current, status);
+                if reached && current != slot {
+                    break;
+                }
+                if !reached {
+                    inner.statuses.insert(current, status);
+                    if status >= commitment {
+                        if let Some(tasks) = inner.pending.remove(&current) {
+                            released.committed.extend(tasks);
+                        }
+                    }
+                }
+                ancestor = inner.parents.get(&current).copied();
+            }
+        }
+
+        if status == Commitment::Rooted {
+            let oldest_tracked = slot.saturating_sub(MAX_TRACKED_SLOTS);
+            let abandoned: Vec<u64> = inner.pending
+                .range(..slot)
+                .map(|(slot, _)| *slot)
+                .filter(|slot| *slot < oldest_tracked || inner.is_abandoned(*slot))
+                .collect();
+            for slot in abandoned {
+                if let Some(tasks) = inner.pending.remove(&slot) {
+                    released.orphaned.extend(tasks);
+                }
+            }
+
+            inner.parents = inner.parents.split_off(&oldest_tracked);
+            inner.statuses = inner.statuses.split_off(&oldest_tracked);
+        }
+        released
+    }
+
+    /// Returns the held tasks whose slot already reached `commitment`, for when the commitmentLevel is reloaded
+    pub(crate) fn release_committed(&self, commitment: Commitment) -> Vec<T> {
+        let mut inner = self.inner.lock().unwrap();
+        let committed: Vec<u64> = inner.pending
+            .keys()
+            .copied()
+            .filter(|slot| inner.reached(*slot, commitment))
+            .collect();
+        committed
+            .into_iter()
+            .filter_map(|slot| inner.pending.remove(&slot))
+            .flatten()
+            .collect()
+    }
+
+    /// Removes all held tasks, returning how many there were
+    pub(crate) fn clear(&self) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        std::mem::take(&mut inner.pending).into_values().map(|tasks| tasks.len()).sum()
+    }
+}
+
+impl<T> Default for CommitmentBuffer<T> {
+    fn default() -> Self {
+        CommitmentBuffer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use crate::commitment_buffer::{Commitment, CommitmentBuffer};
+
+    #[test]
+    fn test_commitment_from_str() {
+        assert_eq!(Commitment::from_str("processed"), Ok(Commitment::Processed));
+        assert_eq!(Commitment::from_str("recent"), Ok(Commitment::Processed));
+        assert_eq!(Commitment::from_str("confirmed"), Ok(Commitment::Confirmed));
+        assert_eq!(Commitment::from_str("finalized"), Ok(Commitment::Rooted));
+        assert_eq!(Commitment::from_str("rooted"), Ok(Commitment::Rooted));
+        assert!(Commitment::from_str("final").is_err());
+    }
+
+    #[test]
+    fn test_processed() {
+        let buffer = CommitmentBuffer::new();
+        assert_eq!(buffer.hold(1, "a", Commitment::Processed), Some("a"));
+    }
+
+    #[test]
+    fn test_confirmed() {
+        let buffer = CommitmentBuffer::new();
+        buffer.update(1, Some(0), Commitment::Processed, Commitment::Confirmed);
+        buffer.update(2, Some(1), Commitment::Processed, Commitment::Confirmed);
+        assert_eq!(buffer.hold(1, "a", Commitment::Confirmed), None);
+        assert_eq!(buffer.hold(2, "b", Commitment::Confirmed), None);
+
+        // Confirming a slot confirms its ancestors
+        let released = buffer.update(2, None, Commitment::Confirmed, Commitment::Confirmed);
+        assert_eq!(released.committed, vec!["b", "a"]);
+        assert!(released.orphaned.is_empty());
+
+        // Transactions notified after their slot is confirmed are not held
+        assert_eq!(buffer.hold(2, "c", Commitment::Confirmed), Some("c"));
+    }
+
+    #[test]
+    fn test_rooted() {
+        let buffer = CommitmentBuffer::new();
+        buffer.update(1, Some(0), Commitment::Processed, Commitment::Rooted);
+        assert_eq!(buffer.hold(1, "a", Commitment::Rooted), None);
+
+        let released = buffer.update(1, None, Commitment::Confirmed, Commitment::Rooted);
+        assert!(released.committed.is_empty());
+
+        let released = buffer.update(1, Some(0), Commitment::Rooted, Commitment::Rooted);
+        assert_eq!(released.committed, vec!["a"]);
+    }
+
+    #[test]
+    fn test_orphaned() {
+        let buffer = CommitmentBuffer::new();
+        // Slots 2 and 3 fork from slot 1, slot 4 builds on slot 3
+        buffer.update(1, Some(0), Commitment::Processed, Commitment::Confirmed);
+        buffer.update(2, Some(1), Commitment::Processed, Commitment::Confirmed);
+        buffer.update(3, Some(1), Commitment::Processed, Commitment::Confirmed);
+        buffer.update(4, Some(3), Commitment::Processed, Commitment::Confirmed);
+        assert_eq!(buffer.hold(2, "abandoned", Commitment::Confirmed), None);
+        assert_eq!(buffer.hold(3, "a", Commitment::Confirmed), None);
+        assert_eq!(buffer.hold(4, "b", Commitment::Confirmed), None);
+
+        let released = buffer.update(3, None, Commitment::Rooted, Commitment::Confirmed);
+        assert_eq!(released.committed, vec!["a"]);
+        assert_eq!(released.orphaned, vec!["abandoned"]);
+        assert_eq!(buffer.clear(), 1);
+    }
+
+    #[test]
+    fn test_unknown_parent_not_orphaned() {
+        let buffer = CommitmentBuffer::new();
+        // The parent of slot 3 is not notified, so slot 2 may still be one of its ancestors
+        buffer.update(2, Some(1), Commitment::Processed, Commitment::Confirmed);
+        assert_eq!(buffer.hold(2, "a", Commitment::Confirmed), None);
+
+        let released = buffer.update(3, None, Commitment::Rooted, Commitment::Confirmed);
+        assert!(released.committed.is_empty());
+        assert!(released.orphaned.is_empty());
+
+        // Once the parent of slot 3 is known, slot 2 is rooted as its ancestor
+        let released = buffer.update(3, Some(2), Commitment::Rooted, Commitment::Confirmed);
+        assert_eq!(released.committed, vec!["a"]);
+        assert!(released.orphaned.is_empty());
+    }
+
+    #[test]
+    fn test_commitment_change() {
+        let buffer = CommitmentBuffer::new();
+        buffer.update(1, Some(0), Commitment::Processed, Commitment::Confirmed);
+        buffer.update(2, Some(1), Commitment::Processed, Commitment::Confirmed);
+        buffer.update(1, None, Commitment::Confirmed, Commitment::Confirmed);
+
+        // Slot 1 is confirmed but not rooted, so its tasks are held once the commitmentLevel is raised to rooted
+        assert_eq!(buffer.hold(1, "a", Commitment::Rooted), None);
+        assert!(buffer.release_committed(Commitment::Rooted).is_empty());
+        let released = buffer.update(1, None, Commitment::Rooted, Commitment::Rooted);
+        assert_eq!(released.committed, vec!["a"]);
+
+        // Lowering the commitmentLevel releases the held tasks of the slots that already reached it
+        assert_eq!(buffer.hold(2, "b", Commitment::Rooted), None);
+        buffer.update(2, None, Commitment::Confirmed, Commitment::Rooted);
+        assert_eq!(buffer.release_committed(Commitment::Confirmed), vec!["b"]);
+        assert_eq!(buffer.clear(), 0);
+    }
+}
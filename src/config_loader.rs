@@ -1,31 +1,113 @@
 use std::env;
-use std::fs::File;
-use std::io::Read;
-use std::path::Path;
-use log::trace;
-use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use log::{trace, LevelFilter};
+use agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use serde_json::error::Category;
+use solana_sdk::pubkey::Pubkey;
 use sologger_log_context::programs_selector::ProgramsSelector;
+use crate::accounts_selector::AccountsSelector;
+use crate::commitment_buffer::Commitment;
+use crate::geyser_plugin_sologger_config::GeyserPluginSologgerConfig;
+use crate::idl::{Idl, IdlRegistry};
 use crate::sologger_config::SologgerConfig;
 
 const DEFAULT_CONFIG_PATH: &str = "/config/local/sologger-config.json";
 const DEFAULT_DIR: &str = "/";
+const SOLOGGER_CONFIG_ENV: &str = "SOLOGGER_APP_CONFIG_LOC";
 
-pub(crate) fn load_config() -> Result<(SologgerConfig, ProgramsSelector)> {
-    let default_config = get_default_config();
-    let sologger_config_path = env::var("SOLOGGER_APP_CONFIG_LOC").unwrap_or(default_config.to_string());
+/// Loads the sologger config for the geyser plugin config file passed to `on_load`.
+/// The sologger config is taken from, in order of precedence: the `SOLOGGER_APP_CONFIG_LOC` environment variable,
+/// the inline `sologger` section of the plugin config, the `sologgerConfigPath` of the plugin config, and finally the default location.
+pub(crate) fn load_config(config_file: &str) -> Result<LoadedConfig, ConfigError> {
+    let override_path = env::var(SOLOGGER_CONFIG_ENV).ok();
+    let source = read_sologger_config(Path::new(config_file), override_path)?;
+    trace!("SologgerConfig: {}", source.contents);
 
-    trace!("sologger_config_path: {}", sologger_config_path);
-    let mut file = File::open(Path::new(sologger_config_path.as_str()))?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)
-        .expect("Failed to read contents of sologger-config.json");
+    let sologger_config: SologgerConfig = parse_json(&source.path, &source.contents)?;
+    let programs_selector = create_programs_selector_from_config(&sologger_config)?;
+    let accounts_selector = create_accounts_selector_from_config(&sologger_config)?;
+    let commitment = parse_commitment_level(&sologger_config)?;
+    check_transport(&sologger_config)?;
+    check_logger_config_files(&sologger_config)?;
+    let idls = load_idls(&sologger_config, &source.file)?;
 
-    let result: serde_json::Value = serde_json::from_str(&contents).unwrap();
-    trace!("SologgerConfig: {}", result.to_string());
-    let programs_selector = create_programs_selector_from_config(&result);
-    let sologger_config = serde_json::from_str(&contents).map_err(|_err| ConfigError::Loading)?;
+    Ok(LoadedConfig { sologger_config, programs_selector, accounts_selector, commitment, idls, file: source.file })
+}
+
+/// A validated sologger config
+pub(crate) struct LoadedConfig {
+    pub(crate) sologger_config: SologgerConfig,
+    pub(crate) programs_selector: ProgramsSelector,
+    pub(crate) accounts_selector: AccountsSelector,
+    pub(crate) commitment: Commitment,
+    pub(crate) idls: IdlRegistry,
+    /// The file the sologger config was read from. For an inline config, this is the geyser plugin config file.
+    pub(crate) file: PathBuf,
+}
 
-    Ok((sologger_config, programs_selector))
+/// The raw contents of a sologger config and where they were read from, used for error reporting.
+struct ConfigSource {
+    path: String,
+    file: PathBuf,
+    contents: String,
+}
+
+fn read_sologger_config(config_file: &Path, override_path: Option<String>) -> Result<ConfigSource, ConfigError> {
+    if let Some(sologger_config_path) = override_path {
+        trace!("Using {} override", SOLOGGER_CONFIG_ENV);
+        return read_file(Path::new(&sologger_config_path));
+    }
+
+    let plugin_config_source = read_file(config_file)?;
+    let plugin_config: GeyserPluginSologgerConfig = parse_json(&plugin_config_source.path, &plugin_config_source.contents)?;
+    match plugin_config {
+        GeyserPluginSologgerConfig { sologger: Some(sologger_config), .. } => Ok(ConfigSource {
+            path: format!("{} (sologger)", plugin_config_source.path),
+            file: plugin_config_source.file,
+            contents: serde_json::to_string_pretty(&sologger_config).unwrap_or_default(),
+        }),
+        GeyserPluginSologgerConfig { sologger_config_path: Some(sologger_config_path), .. } => {
+            read_file(&resolve_relative_to(config_file, &sologger_config_path))
+        }
+        _ => read_file(Path::new(&get_default_config())),
+    }
+}
+
+fn read_file(path: &Path) -> Result<ConfigSource, ConfigError> {
+    trace!("sologger_config_path: {}", path.display());
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(ConfigSource { path: path.display().to_string(), file: path.to_path_buf(), contents }),
+        Err(err) => Err(ConfigError::MissingFile { path: path.display().to_string(), msg: err.to_string() }),
+    }
+}
+
+fn parse_json<T: serde::de::DeserializeOwned>(path: &str, contents: &str) -> Result<T, ConfigError> {
+    serde_json::from_str(contents).map_err(|err| {
+        let path = path.to_string();
+        let (line, column) = (err.line(), err.column());
+        // serde_json appends the location to its messages, it is reported separately
+        let msg = err.to_string();
+        let msg = msg.strip_suffix(&format!(" at line {} column {}", line, column)).unwrap_or(&msg).to_string();
+        match err.classify() {
+            Category::Data if msg.starts_with("unknown field") => ConfigError::UnknownField { path, line, column, msg },
+            Category::Data => ConfigError::InvalidField { path, line, column, msg },
+            Category::Io | Category::Syntax | Category::Eof => ConfigError::InvalidJson { path, line, column, msg },
+        }
+    })
+}
+
+/// Resolves `path` against the directory containing `config_file`, unless `path` is already absolute.
+fn resolve_relative_to(config_file: &Path, path: &str) -> PathBuf {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+    match config_file.parent() {
+        Some(parent) => parent.join(path),
+        None => path.to_path_buf(),
+    }
 }
 
 fn get_default_config() -> String {
@@ -43,31 +125,161 @@ fn get_parent_dir() -> String {
     }
 }
 
-fn create_programs_selector_from_config(config: &serde_json::Value) -> ProgramsSelector {
-    let programs_selector = &config["programsSelector"];
+fn create_programs_selector_from_config(config: &SologgerConfig) -> Result<ProgramsSelector, ConfigError> {
+    match &config.programs_selector {
+        None => Ok(ProgramsSelector::default()),
+        Some(programs_selector) => {
+            let programs = programs_selector.program_ids();
+            let invalid_program_ids = invalid_pubkeys(&programs);
+            if !invalid_program_ids.is_empty() {
+                return Err(ConfigError::InvalidProgramIds { program_ids: invalid_program_ids });
+            }
 
-    if programs_selector.is_null() {
-        ProgramsSelector::default()
-    } else {
-        let programs = &programs_selector["programs"];
-        let programs: Vec<String> = if programs.is_array() {
-            programs
-                .as_array()
-                .unwrap()
-                .iter()
-                .map(|val| val.as_str().unwrap().to_string())
-                .collect()
-        } else {
-            Vec::default()
-        };
+            Ok(ProgramsSelector::new(&programs))
+        }
+    }
+}
+
+fn create_accounts_selector_from_config(config: &SologgerConfig) -> Result<AccountsSelector, ConfigError> {
+    match &config.accounts_selector {
+        None => Ok(AccountsSelector::default()),
+        Some(accounts_selector) => {
+            let mut invalid_account_ids = invalid_pubkeys(&accounts_selector.accounts);
+            invalid_account_ids.extend(invalid_pubkeys(&accounts_selector.owners));
+            if !invalid_account_ids.is_empty() {
+                return Err(ConfigError::InvalidAccountIds { pubkeys: invalid_account_ids });
+            }
+
+            Ok(AccountsSelector::new(&accounts_selector.accounts, &accounts_selector.owners))
+        }
+    }
+}
+
+/// Returns the entries that are neither a base58 encoded pubkey nor "*"
+fn invalid_pubkeys(pubkeys: &[String]) -> Vec<String> {
+    pubkeys
+        .iter()
+        .filter(|pubkey| *pubkey != "*" && Pubkey::from_str(pubkey).is_err())
+        .cloned()
+        .collect()
+}
+
+/// Parses the commitmentLevel, defaulting to processed
+fn parse_commitment_level(config: &SologgerConfig) -> Result<Commitment, ConfigError> {
+    match &config.commitment_level {
+        None => Ok(Commitment::default()),
+        Some(commitment_level) => Commitment::from_str(commitment_level)
+            .map_err(|_| ConfigError::InvalidCommitmentLevel { commitment_level: commitment_level.clone() }),
+    }
+}
+
+/// Reads the IDL files of the `idls` section, resolving relative paths against the sologger config file
+fn load_idls(config: &SologgerConfig, config_file: &Path) -> Result<IdlRegistry, ConfigError> {
+    let mut program_ids: Vec<&String> = config.idls.keys().collect();
+    program_ids.sort();
+    let invalid_program_ids: Vec<String> = program_ids
+        .iter()
+        .filter(|program_id| Pubkey::from_str(program_id).is_err())
+        .map(|program_id| program_id.to_string())
+        .collect();
+    if !invalid_program_ids.is_empty() {
+        return Err(ConfigError::InvalidIdlProgramIds { program_ids: invalid_program_ids });
+    }
+
+    let mut idls = IdlRegistry::default();
+    for program_id in program_ids {
+        let source = read_file(&resolve_relative_to(config_file, &config.idls[program_id]))?;
+        let idl: Idl = parse_json(&source.path, &source.contents)?;
+        idl.validate().map_err(|msg| ConfigError::InvalidIdl { path: source.path.clone(), msg })?;
+        idls.insert(Pubkey::from_str(program_id).unwrap(), idl);
+    }
+    Ok(idls)
+}
 
-        ProgramsSelector::new(&programs)
+/// Checks that the transports requested in the transport section were compiled in, and that the filters of each sink are valid
+fn check_transport(config: &SologgerConfig) -> Result<(), ConfigError> {
+    let transport = config.transport();
+    if transport.logstash.is_some() && !cfg!(feature = "enable_logstash") {
+        return Err(ConfigError::TransportNotCompiled { transport: "logstash", feature: "enable_logstash" });
+    }
+    if transport.otel.is_some() && !cfg!(feature = "enable_otel") {
+        return Err(ConfigError::TransportNotCompiled { transport: "otel", feature: "enable_otel" });
+    }
+    if config.traces && !cfg!(feature = "enable_otel") {
+        return Err(ConfigError::TransportNotCompiled { transport: "traces", feature: "enable_otel" });
+    }
+    if config.metrics && !cfg!(feature = "enable_otel") {
+        return Err(ConfigError::TransportNotCompiled { transport: "metrics", feature: "enable_otel" });
+    }
+    if config.metrics && config.metrics_export_interval_secs == 0 {
+        return Err(ConfigError::InvalidMetricsExportInterval);
+    }
+    for (name, sink) in transport.sinks() {
+        let invalid_program_ids = invalid_pubkeys(&sink.programs);
+        if !invalid_program_ids.is_empty() {
+            return Err(ConfigError::InvalidSinkProgramIds { transport: name, program_ids: invalid_program_ids });
+        }
+        if let Some(level) = sink.level.filter(|level| LevelFilter::from_str(level).is_err()) {
+            return Err(ConfigError::InvalidSinkLevel { transport: name, level });
+        }
+        if sink.max_queued_logs == Some(0) {
+            return Err(ConfigError::InvalidSinkQueueSize { transport: name });
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the log4rs and OpenTelemetry config files exist when they are configured or required by the transport, so the logger can be initialized.
+fn check_logger_config_files(config: &SologgerConfig) -> Result<(), ConfigError> {
+    let transport = config.transport();
+    let log4rs_required = transport.log4rs.is_some() || transport.logstash.is_some();
+    if (log4rs_required || !config.log4rs_config_location.is_empty())
+        && !Path::new(&config.log4rs_config_location).exists() {
+        return Err(ConfigError::MissingLog4rsFile { path: config.log4rs_config_location.clone() });
+    }
+    let otel_required = transport.otel.is_some() || config.traces || config.metrics;
+    if (otel_required || !config.opentelemetry_config_location.is_empty())
+        && !Path::new(&config.opentelemetry_config_location).exists() {
+        return Err(ConfigError::MissingOtelFile { path: config.opentelemetry_config_location.clone() });
     }
+    Ok(())
 }
 
+/// Errors that can occur while loading the sologger config. These are reported to the validator as a failed plugin load.
 #[derive(Debug)]
-enum ConfigError {
-    Loading,
+pub(crate) enum ConfigError {
+    /// A config file does not exist or could not be read
+    MissingFile { path: String, msg: String },
+    /// A config file is not valid JSON
+    InvalidJson { path: String, line: usize, column: usize, msg: String },
+    /// A config file contains a field that sologger does not know about, usually a typo
+    UnknownField { path: String, line: usize, column: usize, msg: String },
+    /// A config field is missing or has the wrong type
+    InvalidField { path: String, line: usize, column: usize, msg: String },
+    /// One or more program IDs in the programsSelector are not valid base58 encoded pubkeys
+    InvalidProgramIds { program_ids: Vec<String> },
+    /// One or more accounts or owners in the accountsSelector are not valid base58 encoded pubkeys
+    InvalidAccountIds { pubkeys: Vec<String> },
+    /// The commitmentLevel is not one of processed, confirmed or finalized
+    InvalidCommitmentLevel { commitment_level: String },
+    /// One or more program IDs in idls are not valid base58 encoded pubkeys
+    InvalidIdlProgramIds { program_ids: Vec<String> },
+    /// An IDL file references a type it does not define
+    InvalidIdl { path: String, msg: String },
+    /// The transport section requests a transport whose cargo feature was not enabled when the plugin was built
+    TransportNotCompiled { transport: &'static str, feature: &'static str },
+    /// One or more program IDs in the programs of a sink are not valid base58 encoded pubkeys
+    InvalidSinkProgramIds { transport: &'static str, program_ids: Vec<String> },
+    /// The level of a sink is not a log level
+    InvalidSinkLevel { transport: &'static str, level: String },
+    /// The maxQueuedLogs of a sink is 0
+    InvalidSinkQueueSize { transport: &'static str },
+    /// The metricsExportIntervalSecs is 0
+    InvalidMetricsExportInterval,
+    /// The log4rs config file referenced by log4rsConfigLocation does not exist
+    MissingLog4rsFile { path: String },
+    /// The OpenTelemetry config file referenced by opentelemetryConfigLocation does not exist
+    MissingOtelFile { path: String },
 }
 
 impl std::error::Error for ConfigError {}
@@ -76,7 +288,296 @@ impl std::fmt::Display for ConfigError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         use ConfigError::*;
         match self {
-            Loading => write!(f, "Loading"),
+            MissingFile { path, msg } => write!(f, "Unable to read config file {}: {}", path, msg),
+            InvalidJson { path, line, column, msg } => {
+                write!(f, "Invalid JSON in {} at line {} column {}: {}", path, line, column, msg)
+            }
+            UnknownField { path, line, column, msg } => {
+                write!(f, "Unknown field in {} at line {} column {}: {}", path, line, column, msg)
+            }
+            InvalidField { path, line, column, msg } => {
+                write!(f, "Invalid field in {} at line {} column {}: {}", path, line, column, msg)
+            }
+            InvalidProgramIds { program_ids } => {
+                write!(f, "Invalid program IDs in programsSelector: {}", program_ids.join(", "))
+            }
+            InvalidAccountIds { pubkeys } => {
+                write!(f, "Invalid pubkeys in accountsSelector: {}", pubkeys.join(", "))
+            }
+            InvalidCommitmentLevel { commitment_level } => {
+                write!(f, "Invalid commitmentLevel {}, expected processed, confirmed or finalized", commitment_level)
+            }
+            InvalidIdlProgramIds { program_ids } => {
+                write!(f, "Invalid program IDs in idls: {}", program_ids.join(", "))
+            }
+            InvalidIdl { path, msg } => write!(f, "Invalid IDL {}: {}", path, msg),
+            TransportNotCompiled { transport, feature } => {
+                write!(f, "The {} transport is not available, the plugin was built without the {} feature", transport, feature)
+            }
+            InvalidSinkProgramIds { transport, program_ids } => {
+                write!(f, "Invalid program IDs in the programs of the {} transport: {}", transport, program_ids.join(", "))
+            }
+            InvalidSinkLevel { transport, level } => {
+                write!(f, "Invalid level {} for the {} transport, expected off, error, warn, info, debug or trace", level, transport)
+            }
+            InvalidSinkQueueSize { transport } => write!(f, "The maxQueuedLogs of the {} transport must be greater than 0", transport),
+            InvalidMetricsExportInterval => write!(f, "The metricsExportIntervalSecs must be greater than 0"),
+            MissingLog4rsFile { path } => write!(f, "Log4rs config file not found: {}", path),
+            MissingOtelFile { path } => write!(f, "OTel config file not found: {}", path),
         }
     }
-}
\ No newline at end of file
+}
+
+impl From<ConfigError> for GeyserPluginError {
+    fn from(err: ConfigError) -> Self {
+        GeyserPluginError::ConfigFileReadError { msg: err.to_string() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::str::FromStr;
+    use serde_json::json;
+    use solana_sdk::pubkey::Pubkey;
+    use crate::config_loader::{check_transport, load_config, load_idls, parse_json, read_sologger_config, ConfigError};
+    use crate::sologger_config::SologgerConfig;
+
+    const SOLOGGER_CONFIG: &str = r#"{
+        "rpcUrl": "wss://api.devnet.solana.com",
+        "accountDataNotificationsEnabled": false,
+        "transactionNotificationsEnabled": true,
+        "logProcessorWorkerThreadCount": 2
+    }"#;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join("sologger-config-loader-tests").join(name);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    pub fn test_inline_config() {
+        let dir = test_dir("inline");
+        let plugin_config = dir.join("plugin-config.json");
+        fs::write(&plugin_config, json!({
+            "libpath": "libsologger_geyser_plugin.so",
+            "sologgerConfigPath": "does-not-exist.json",
+            "sologger": { "rpcUrl": "wss://api.mainnet-beta.solana.com" }
+        }).to_string()).unwrap();
+
+        let source = read_sologger_config(&plugin_config, None).unwrap();
+        assert!(source.contents.contains("wss://api.mainnet-beta.solana.com"));
+    }
+
+    #[test]
+    pub fn test_relative_config_path() {
+        let dir = test_dir("relative");
+        fs::create_dir_all(dir.join("local")).unwrap();
+        fs::write(dir.join("local/sologger-config.json"), SOLOGGER_CONFIG).unwrap();
+        let plugin_config = dir.join("plugin-config.json");
+        fs::write(&plugin_config, json!({
+            "libpath": "libsologger_geyser_plugin.so",
+            "sologgerConfigPath": "local/sologger-config.json"
+        }).to_string()).unwrap();
+
+        let source = read_sologger_config(&plugin_config, None).unwrap();
+        assert_eq!(source.contents, SOLOGGER_CONFIG);
+    }
+
+    #[test]
+    pub fn test_env_override() {
+        let dir = test_dir("override");
+        let sologger_config = dir.join("sologger-config.json");
+        fs::write(&sologger_config, SOLOGGER_CONFIG).unwrap();
+        let plugin_config = dir.join("plugin-config.json");
+        fs::write(&plugin_config, json!({
+            "libpath": "libsologger_geyser_plugin.so",
+            "sologger": { "rpcUrl": "wss://api.mainnet-beta.solana.com" }
+        }).to_string()).unwrap();
+
+        let override_path = Some(sologger_config.display().to_string());
+        let source = read_sologger_config(&plugin_config, override_path).unwrap();
+        assert_eq!(source.contents, SOLOGGER_CONFIG);
+    }
+
+    #[test]
+    pub fn test_missing_file() {
+        let dir = test_dir("missing");
+        let plugin_config = dir.join("plugin-config.json");
+        fs::write(&plugin_config, json!({
+            "libpath": "libsologger_geyser_plugin.so",
+            "sologgerConfigPath": "does-not-exist.json"
+        }).to_string()).unwrap();
+
+        let err = read_sologger_config(&plugin_config, None).err().unwrap();
+        assert!(matches!(err, ConfigError::MissingFile { .. }));
+    }
+
+    #[test]
+    pub fn test_invalid_json() {
+        let contents = "{\n    \"rpcUrl\": \"wss://api.devnet.solana.com\",\n}";
+        let err = parse_json::<SologgerConfig>("sologger-config.json", contents).err().unwrap();
+        match err {
+            ConfigError::InvalidJson { line, column, .. } => assert_eq!((line, column), (3, 1)),
+            err => panic!("Unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    pub fn test_unknown_field() {
+        let contents = SOLOGGER_CONFIG.replace("rpcUrl", "rpcURL");
+        let err = parse_json::<SologgerConfig>("sologger-config.json", &contents).err().unwrap();
+        assert!(matches!(err, ConfigError::UnknownField { line: 2, .. }));
+        assert!(err.to_string().starts_with("Unknown field in sologger-config.json at line 2"));
+    }
+
+    #[test]
+    pub fn test_invalid_program_id() {
+        let dir = test_dir("invalid-program-id");
+        let plugin_config = dir.join("plugin-config.json");
+        fs::write(&plugin_config, json!({
+            "libpath": "libsologger_geyser_plugin.so",
+            "sologger": {
+                "rpcUrl": "wss://api.devnet.solana.com",
+                "programsSelector": {
+                    "programs": ["*", "Tokenkeg0OIl", "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin"],
+                    "aliases": { "short": "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9Pus" }
+                },
+                "accountDataNotificationsEnabled": false,
+                "transactionNotificationsEnabled": true,
+                "logProcessorWorkerThreadCount": 2
+            }
+        }).to_string()).unwrap();
+
+        std::env::remove_var("SOLOGGER_APP_CONFIG_LOC");
+        let err = load_config(plugin_config.to_str().unwrap()).err().unwrap();
+        match err {
+            ConfigError::InvalidProgramIds { program_ids } => {
+                assert_eq!(program_ids, vec!["Tokenkeg0OIl", "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9Pus"])
+            }
+            err => panic!("Unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    pub fn test_invalid_commitment_level() {
+        let dir = test_dir("invalid-commitment-level");
+        let plugin_config = dir.join("plugin-config.json");
+        fs::write(&plugin_config, json!({
+            "libpath": "libsologger_geyser_plugin.so",
+            "sologger": {
+                "rpcUrl": "wss://api.devnet.solana.com",
+                "commitmentLevel": "final",
+                "accountDataNotificationsEnabled": false,
+                "transactionNotificationsEnabled": true,
+                "logProcessorWorkerThreadCount": 2
+            }
+        }).to_string()).unwrap();
+
+        std::env::remove_var("SOLOGGER_APP_CONFIG_LOC");
+        let err = load_config(plugin_config.to_str().unwrap()).err().unwrap();
+        assert!(matches!(err, ConfigError::InvalidCommitmentLevel { .. }));
+        assert_eq!(err.to_string(), "Invalid commitmentLevel final, expected processed, confirmed or finalized");
+    }
+
+    #[test]
+    pub fn test_invalid_accounts_selector() {
+        let dir = test_dir("invalid-accounts-selector");
+        let plugin_config = dir.join("plugin-config.json");
+        fs::write(&plugin_config, json!({
+            "libpath": "libsologger_geyser_plugin.so",
+            "sologger": {
+                "rpcUrl": "wss://api.devnet.solana.com",
+                "accountsSelector": {
+                    "accounts": ["9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin", "not-a-pubkey"],
+                    "owners": ["*", "Tokenkeg0OIl"]
+                },
+                "accountDataNotificationsEnabled": true,
+                "transactionNotificationsEnabled": true,
+                "logProcessorWorkerThreadCount": 2
+            }
+        }).to_string()).unwrap();
+
+        std::env::remove_var("SOLOGGER_APP_CONFIG_LOC");
+        let err = load_config(plugin_config.to_str().unwrap()).err().unwrap();
+        assert_eq!(err.to_string(), "Invalid pubkeys in accountsSelector: not-a-pubkey, Tokenkeg0OIl");
+    }
+
+    #[test]
+    pub fn test_load_idls() {
+        let dir = test_dir("idls");
+        fs::create_dir_all(dir.join("idls")).unwrap();
+        fs::write(dir.join("idls/counter.json"), json!({
+            "accounts": [{ "name": "Counter", "discriminator": [1, 2, 3, 4, 5, 6, 7, 8] }],
+            "types": [{ "name": "Counter", "type": { "kind": "struct", "fields": [{ "name": "count", "type": "u64" }] } }]
+        }).to_string()).unwrap();
+        fs::write(dir.join("idls/invalid.json"), json!({
+            "accounts": [{ "name": "Counter", "discriminator": [1, 2, 3, 4, 5, 6, 7, 8] }]
+        }).to_string()).unwrap();
+        let sologger_config_file = dir.join("sologger-config.json");
+
+        let mut sologger_config: SologgerConfig = serde_json::from_str(SOLOGGER_CONFIG).unwrap();
+        sologger_config.idls.insert("9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin".to_string(), "idls/counter.json".to_string());
+        let idls = load_idls(&sologger_config, &sologger_config_file).unwrap();
+        let owner = Pubkey::from_str("9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin").unwrap();
+        assert!(idls.decode_account(owner.as_ref(), &[1, 2, 3, 4, 5, 6, 7, 8, 42, 0, 0, 0, 0, 0, 0, 0]).unwrap().is_ok());
+
+        sologger_config.idls.insert("not-a-pubkey".to_string(), "idls/counter.json".to_string());
+        let err = load_idls(&sologger_config, &sologger_config_file).err().unwrap();
+        assert_eq!(err.to_string(), "Invalid program IDs in idls: not-a-pubkey");
+
+        sologger_config.idls.clear();
+        sologger_config.idls.insert("9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin".to_string(), "idls/invalid.json".to_string());
+        let err = load_idls(&sologger_config, &sologger_config_file).err().unwrap();
+        assert!(matches!(err, ConfigError::InvalidIdl { .. }));
+        assert!(err.to_string().ends_with("No type definition for account Counter"));
+    }
+
+    #[test]
+    pub fn test_transport_not_compiled() {
+        let dir = test_dir("transport-not-compiled");
+        let plugin_config = dir.join("plugin-config.json");
+        fs::write(&plugin_config, json!({
+            "libpath": "libsologger_geyser_plugin.so",
+            "sologger": {
+                "rpcUrl": "wss://api.devnet.solana.com",
+                "transport": { "otel": {} },
+                "accountDataNotificationsEnabled": false,
+                "transactionNotificationsEnabled": true,
+                "logProcessorWorkerThreadCount": 2
+            }
+        }).to_string()).unwrap();
+
+        std::env::remove_var("SOLOGGER_APP_CONFIG_LOC");
+        let err = load_config(plugin_config.to_str().unwrap()).err().unwrap();
+        if cfg!(feature = "enable_otel") {
+            assert!(matches!(err, ConfigError::MissingOtelFile { .. }));
+        } else {
+            assert_eq!(err.to_string(), "The otel transport is not available, the plugin was built without the enable_otel feature");
+        }
+    }
+
+    #[test]
+    pub fn test_invalid_sink_filters() {
+        let mut sologger_config: SologgerConfig = serde_json::from_str(SOLOGGER_CONFIG).unwrap();
+        sologger_config.transport = Some(serde_json::from_value(json!({
+            "stdout": { "programs": ["*", "not-a-pubkey"] }
+        })).unwrap());
+        let err = check_transport(&sologger_config).err().unwrap();
+        assert_eq!(err.to_string(), "Invalid program IDs in the programs of the stdout transport: not-a-pubkey");
+
+        sologger_config.transport = Some(serde_json::from_value(json!({
+            "file": { "path": "./sologger.ndjson", "programs": ["TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"], "level": "verbose" }
+        })).unwrap());
+        let err = check_transport(&sologger_config).err().unwrap();
+        assert!(matches!(err, ConfigError::InvalidSinkLevel { transport: "file", .. }));
+
+        sologger_config.transport = Some(serde_json::from_value(json!({
+            "stdout": { "level": "warn", "maxQueuedLogs": 0 }
+        })).unwrap());
+        let err = check_transport(&sologger_config).err().unwrap();
+        assert_eq!(err.to_string(), "The maxQueuedLogs of the stdout transport must be greater than 0");
+    }
+}
@@ -0,0 +1,190 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+use log::{error, info, trace};
+
+use crate::config_loader;
+use crate::geyser_plugin_sologger::PluginContext;
+use crate::sologger_config::SologgerConfig;
+
+/// Starts a thread that polls the geyser plugin config and the sologger config for changes every `interval`,
+/// and applies the new config to the running plugin. If the new config fails to load, the current config is kept.
+/// The thread exits once the plugin context or `watcher_running` stops running; unpark it to stop it without waiting for the next poll.
+pub(crate) fn spawn_config_watcher(
+    context: PluginContext,
+    watcher_running: Arc<AtomicBool>,
+    config_file: String,
+    sologger_config_file: PathBuf,
+    interval: Duration,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        info!("Watching {} for config changes every {:?}", sologger_config_file.display(), interval);
+        let mut files = vec![PathBuf::from(&config_file), sologger_config_file];
+        let mut modified = last_modified(&files);
+
+        let is_running = || context.running.load(Ordering::SeqCst) && watcher_running.load(Ordering::SeqCst);
+        while is_running() {
+            thread::park_timeout(interval);
+            if !is_running() {
+                break;
+            }
+
+            let current = last_modified(&files);
+            if current == modified {
+                continue;
+            }
+            modified = current;
+            trace!("Config change detected");
+
+            match config_loader::load_config(&config_file) {
+                Ok(loaded_config) => {
+                    files[1] = loaded_config.file.clone();
+                    modified = last_modified(&files);
+                    context.apply_config(loaded_config);
+                }
+                Err(err) => {
+                    error!("Error reloading sologger config, keeping the current config: {}", err);
+                }
+            }
+        }
+        info!("Config watcher shutting down");
+    })
+}
+
+fn last_modified(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| fs::metadata(file).and_then(|metadata| metadata.modified()).ok())
+        .collect()
+}
+
+/// The top level config fields that are only read when the plugin is loaded, so changing them requires a restart
+const LOAD_TIME_FIELDS: &[&str] = &[
+    "accountDataNotificationsEnabled",
+    "configReloadIntervalSecs",
+    "log4rsConfigLocation",
+    "maxQueuedTasks",
+    "metrics",
+    "metricsExportIntervalSecs",
+    "opentelemetryConfigLocation",
+    "traces",
+    "transactionNotificationsEnabled",
+];
+
+/// Returns the top level config fields that differ between the two configs, but are not applied until the plugin is restarted.
+/// The level and program filters of the transports are applied on reload, while adding or removing a transport, or changing the file path or maxQueuedLogs of one, requires a restart.
+pub(crate) fn requires_restart(old: &SologgerConfig, new: &SologgerConfig) -> Vec<&'static str> {
+    let without_filters = |config: &SologgerConfig| {
+        let mut transport = serde_json::to_value(config.transport()).unwrap_or_default();
+        for sink in transport.as_object_mut().into_iter().flat_map(|sinks| sinks.values_mut()) {
+            if let Some(sink) = sink.as_object_mut() {
+                sink.remove("programs");
+                sink.remove("level");
+            }
+        }
+        transport
+    };
+    let old_value = serde_json::to_value(old).unwrap_or_default();
+    let new_value = serde_json::to_value(new).unwrap_or_default();
+    let mut fields: Vec<&'static str> = LOAD_TIME_FIELDS
+        .iter()
+        .copied()
+        .filter(|field| old_value.get(field) != new_value.get(field))
+        .collect();
+    if without_filters(old) != without_filters(new) {
+        fields.push("transport");
+        fields.sort();
+    }
+    fields
+}
+
+/// Returns a description of each top level config field that differs between the two configs, except for the `ignored` fields
+pub(crate) fn config_diff(old: &SologgerConfig, new: &SologgerConfig, ignored: &[&str]) -> Vec<String> {
+    let old = serde_json::to_value(old).unwrap_or_default();
+    let new = serde_json::to_value(new).unwrap_or_default();
+    let (Some(old), Some(new)) = (old.as_object(), new.as_object()) else {
+        return vec![];
+    };
+
+    let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys.retain(|key| !ignored.contains(&key.as_str()));
+    keys.into_iter()
+        .filter_map(|key| {
+            let old_value = old.get(key).unwrap_or(&serde_json::Value::Null);
+            let new_value = new.get(key).unwrap_or(&serde_json::Value::Null);
+            if old_value == new_value {
+                None
+            } else {
+                Some(format!("{}: {} -> {}", key, old_value, new_value))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use crate::config_watcher::{config_diff, requires_restart};
+    use crate::sologger_config::{SinkConfig, SologgerConfig, TransportConfig};
+
+    #[test]
+    pub fn test_config_diff() {
+        let old = serde_json::from_value::<SologgerConfig>(json!(
+            {
+                "rpcUrl": "wss://api.devnet.solana.com",
+                "programsSelector" : { "programs" : ["*"] },
+                "accountDataNotificationsEnabled": false,
+                "transactionNotificationsEnabled": true,
+                "logProcessorWorkerThreadCount": 2
+            }
+        )).unwrap();
+        let mut new = old.clone();
+        assert!(config_diff(&old, &new, &[]).is_empty());
+
+        new.log_processor_worker_thread_count = 4;
+        new.programs_selector = None;
+        assert_eq!(
+            config_diff(&old, &new, &[]),
+            vec![
+                "logProcessorWorkerThreadCount: 2 -> 4",
+                "programsSelector: {\"aliases\":{},\"programs\":[\"*\"]} -> null",
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_requires_restart() {
+        let old = serde_json::from_value::<SologgerConfig>(json!(
+            {
+                "rpcUrl": "wss://api.devnet.solana.com",
+                "accountDataNotificationsEnabled": false,
+                "transactionNotificationsEnabled": true,
+                "logProcessorWorkerThreadCount": 2,
+                "transport": { "stdout": {} }
+            }
+        )).unwrap();
+        let mut new = old.clone();
+        new.log_processor_worker_thread_count = 4;
+        new.transport = Some(TransportConfig {
+            stdout: Some(SinkConfig { level: Some("error".to_string()), programs: vec!["11111111111111111111111111111111".to_string()], ..Default::default() }),
+            ..Default::default()
+        });
+        assert!(requires_restart(&old, &new).is_empty());
+
+        new.max_queued_tasks = Some(100);
+        new.traces = true;
+        new.transport.as_mut().unwrap().stdout.as_mut().unwrap().max_queued_logs = Some(100);
+        assert_eq!(requires_restart(&old, &new), vec!["maxQueuedTasks", "traces", "transport"]);
+        assert_eq!(
+            config_diff(&old, &new, &requires_restart(&old, &new)),
+            vec!["logProcessorWorkerThreadCount: 2 -> 4"]
+        );
+    }
+}
@@ -0,0 +1,290 @@
+use std::str::FromStr;
+
+use agave_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfoVersions;
+use log::info;
+use serde::{Serialize, Serializer};
+use solana_sdk::clock::UnixTimestamp;
+use solana_sdk::instruction::CompiledInstruction;
+use solana_sdk::message::AccountKeys;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::inner_transaction::ReplicaTransactionInfo;
+
+/// An instruction of a transaction with the inner instructions it invoked through CPI.
+/// The account keys are resolved when the transaction is notified, and converted to strings only when the tree is emitted.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CpiInstruction {
+    /// The index of the instruction in the transaction for outer instructions, or among the inner instructions of its outer instruction
+    index: usize,
+    #[serde(serialize_with = "serialize_pubkey")]
+    program_id: Pubkey,
+    /// 1 for outer instructions. Not available for inner instructions from older validators.
+    stack_height: Option<u32>,
+    #[serde(serialize_with = "serialize_pubkeys")]
+    accounts: Vec<Pubkey>,
+    /// The base58 encoded instruction data
+    #[serde(serialize_with = "serialize_base58")]
+    data: Vec<u8>,
+    /// The start (inclusive) and end (exclusive) of the invocation's lines in the transaction's log messages, from `invoke` to `success` or `failed`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    log_range: Option<(usize, usize)>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    inner_instructions: Vec<CpiInstruction>,
+}
+
+impl CpiInstruction {
+    fn new(index: usize, instruction: &CompiledInstruction, stack_height: Option<u32>, account_keys: &AccountKeys) -> Self {
+        let account_key = |index: u8| account_keys.get(index as usize).copied().unwrap_or_default();
+        CpiInstruction {
+            index,
+            program_id: account_key(instruction.program_id_index),
+            stack_height,
+            accounts: instruction.accounts.iter().map(|index| account_key(*index)).collect(),
+            data: instruction.data.clone(),
+            log_range: None,
+            inner_instructions: vec![],
+        }
+    }
+
+    /// Adds the inner instruction below the last instruction invoked one level up the stack. `depth` is the stack height of this instruction.
+    fn insert(&mut self, instruction: CpiInstruction, depth: u32) {
+        let stack_height = instruction.stack_height.unwrap_or(depth + 1);
+        match self.inner_instructions.last_mut() {
+            Some(last) if stack_height > depth + 1 => last.insert(instruction, depth + 1),
+            _ => self.inner_instructions.push(instruction),
+        }
+    }
+}
+
+/// Returns the outer instructions of the transaction, with their inner instructions nested by stack height
+pub fn cpi_tree(transaction: &ReplicaTransactionInfoVersions) -> Vec<CpiInstruction> {
+    let (sanitized_transaction, inner_instructions) = match transaction {
+        ReplicaTransactionInfoVersions::V0_0_1(transaction) => {
+            (transaction.transaction, &transaction.transaction_status_meta.inner_instructions)
+        }
+        ReplicaTransactionInfoVersions::V0_0_2(transaction) => {
+            (transaction.transaction, &transaction.transaction_status_meta.inner_instructions)
+        }
+    };
+    let account_keys = sanitized_transaction.message().account_keys();
+    let mut instructions: Vec<CpiInstruction> = sanitized_transaction.message()
+        .instructions()
+        .iter()
+        .enumerate()
+        .map(|(index, instruction)| CpiInstruction::new(index, instruction, Some(1), &account_keys))
+        .collect();
+    for inner_instructions in inner_instructions.iter().flatten() {
+        if let Some(outer_instruction) = instructions.get_mut(inner_instructions.index as usize) {
+            for (index, inner_instruction) in inner_instructions.instructions.iter().enumerate() {
+                let instruction = CpiInstruction::new(index, &inner_instruction.instruction, inner_instruction.stack_height, &account_keys);
+                outer_instruction.insert(instruction, 1);
+            }
+        }
+    }
+    instructions
+}
+
+/// Sets the log range of each instruction from the `invoke` logs, which follow the order in which the instructions were invoked.
+/// Matching stops at the first invocation of another program than expected, for example when the logs were truncated.
+pub fn set_log_ranges(instructions: &mut [CpiInstruction], log_messages: &[String]) {
+    match_invocations(instructions, &mut invocations(log_messages).into_iter());
+}
+
+/// Matches the instructions and their inner instructions, depth first, with the invocations. Returns false once an invocation does not match.
+fn match_invocations(instructions: &mut [CpiInstruction], invocations: &mut impl Iterator<Item = (Pubkey, (usize, usize))>) -> bool {
+    for instruction in instructions {
+        match invocations.next() {
+            Some((program_id, log_range)) if program_id == instruction.program_id => instruction.log_range = Some(log_range),
+            _ => return false,
+        }
+        if !match_invocations(&mut instruction.inner_instructions, invocations) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Returns the program ID and log range of each invocation in the logs, in the order they were invoked
+fn invocations(log_messages: &[String]) -> Vec<(Pubkey, (usize, usize))> {
+    let mut invocations = Vec::new();
+    let mut stack = Vec::new();
+    for (line, log) in log_messages.iter().enumerate() {
+        let Some((program_id, result)) = log.strip_prefix("Program ").and_then(|log| log.split_once(' ')) else {
+            continue;
+        };
+        let Ok(program_id) = Pubkey::from_str(program_id) else {
+            continue;
+        };
+        if result.starts_with("invoke [") {
+            stack.push(invocations.len());
+            invocations.push((program_id, (line, log_messages.len())));
+        } else if result == "success" || result.starts_with("failed: ") {
+            if let Some(invocation) = stack.pop() {
+                invocations[invocation].1.1 = line + 1;
+            }
+        }
+    }
+    invocations
+}
+
+/// The instruction tree of a transaction and its log messages.
+/// Emitted as JSON on the `sologger_geyser_plugin::geyser_plugin_sologger::notify_transaction` log target.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct CpiTreeEvent<'a> {
+    event: &'static str,
+    signature: String,
+    slot: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    block_time: Option<UnixTimestamp>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    orphaned: bool,
+    instructions: &'a [CpiInstruction],
+    log_messages: &'a [String],
+}
+
+/// Logs the instruction tree of the transaction, with the log range of each invocation
+pub(crate) fn emit_cpi_tree(transaction: &ReplicaTransactionInfo, instructions: &mut [CpiInstruction]) {
+    set_log_ranges(instructions, &transaction.log_messages);
+    let event = CpiTreeEvent {
+        event: "cpiTree",
+        signature: transaction.signature.to_string(),
+        slot: transaction.slot,
+        block_time: transaction.block_time,
+        orphaned: transaction.orphaned,
+        instructions,
+        log_messages: &transaction.log_messages,
+    };
+    info!(target: "sologger_geyser_plugin::geyser_plugin_sologger::notify_transaction", "{}", serde_json::to_string(&event).unwrap_or_default());
+}
+
+fn serialize_pubkey<S: Serializer>(pubkey: &Pubkey, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(pubkey)
+}
+
+fn serialize_pubkeys<S: Serializer>(pubkeys: &[Pubkey], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(pubkeys.iter().map(|pubkey| pubkey.to_string()))
+}
+
+fn serialize_base58<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&bs58::encode(data).into_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use agave_geyser_plugin_interface::geyser_plugin_interface::{ReplicaTransactionInfoV2, ReplicaTransactionInfoVersions};
+    use serde_json::json;
+    use solana_sdk::instruction::{AccountMeta, CompiledInstruction, Instruction};
+    use solana_sdk::message::Message;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::transaction::{SanitizedTransaction, Transaction};
+    use solana_transaction_status::{InnerInstruction, InnerInstructions, TransactionStatusMeta};
+    use crate::cpi_tree::{cpi_tree, set_log_ranges};
+
+    #[test]
+    fn test_cpi_tree() {
+        let payer = Pubkey::new_unique();
+        let (a, b, c, d) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let message = Message::new(&[
+            Instruction::new_with_bytes(a, &[1, 2, 3], vec![AccountMeta::new_readonly(b, false), AccountMeta::new_readonly(c, false)]),
+            Instruction::new_with_bytes(d, &[], vec![]),
+        ], Some(&payer));
+        let index = |pubkey: &Pubkey| message.account_keys.iter().position(|key| key == pubkey).unwrap() as u8;
+        let inner_instruction = |program_id: &Pubkey, stack_height: u32| InnerInstruction {
+            instruction: CompiledInstruction::new_from_raw_parts(index(program_id), vec![stack_height as u8], vec![index(&payer)]),
+            stack_height: Some(stack_height),
+        };
+        let transaction_status_meta = TransactionStatusMeta {
+            inner_instructions: Some(vec![InnerInstructions {
+                index: 0,
+                instructions: vec![inner_instruction(&b, 2), inner_instruction(&c, 3), inner_instruction(&b, 2)],
+            }]),
+            ..Default::default()
+        };
+        let transaction = SanitizedTransaction::from_transaction_for_tests(Transaction::new_unsigned(message.clone()));
+        let transaction_info = ReplicaTransactionInfoV2 {
+            signature: &Default::default(),
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &transaction_status_meta,
+            index: 0,
+        };
+
+        let mut instructions = cpi_tree(&ReplicaTransactionInfoVersions::V0_0_2(&transaction_info));
+        let logs: Vec<String> = vec![
+            format!("Program {} invoke [1]", a),
+            format!("Program {} invoke [2]", b),
+            format!("Program {} invoke [3]", c),
+            format!("Program {} success", c),
+            format!("Program {} success", b),
+            format!("Program {} invoke [2]", b),
+            "Program log: invoke [1]".to_string(),
+            format!("Program {} success", b),
+            format!("Program {} consumed 100 of 200000 compute units", a),
+            format!("Program {} success", a),
+            format!("Program {} invoke [1]", d),
+            "Log truncated".to_string(),
+        ];
+        set_log_ranges(&mut instructions, &logs);
+
+        let inner_json = |program_id: &Pubkey, index: usize, stack_height: u32, log_range: (usize, usize)| json!({
+            "index": index,
+            "programId": program_id.to_string(),
+            "stackHeight": stack_height,
+            "accounts": [payer.to_string()],
+            "data": bs58::encode([stack_height as u8]).into_string(),
+            "logRange": [log_range.0, log_range.1],
+        });
+        let mut first_inner = inner_json(&b, 0, 2, (1, 5));
+        first_inner["innerInstructions"] = json!([inner_json(&c, 1, 3, (2, 4))]);
+        assert_eq!(serde_json::to_value(&instructions).unwrap(), json!([
+            {
+                "index": 0,
+                "programId": a.to_string(),
+                "stackHeight": 1,
+                "accounts": [b.to_string(), c.to_string()],
+                "data": bs58::encode([1, 2, 3]).into_string(),
+                "logRange": [0, 10],
+                "innerInstructions": [first_inner, inner_json(&b, 2, 2, (5, 8))],
+            },
+            {
+                "index": 1,
+                "programId": d.to_string(),
+                "stackHeight": 1,
+                "accounts": [],
+                "data": "",
+                "logRange": [10, 12],
+            },
+        ]));
+    }
+
+    #[test]
+    fn test_log_ranges_mismatch() {
+        let payer = Pubkey::new_unique();
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let message = Message::new(&[
+            Instruction::new_with_bytes(a, &[], vec![]),
+            Instruction::new_with_bytes(b, &[], vec![]),
+        ], Some(&payer));
+        let transaction = SanitizedTransaction::from_transaction_for_tests(Transaction::new_unsigned(message));
+        let transaction_status_meta = TransactionStatusMeta::default();
+        let transaction_info = ReplicaTransactionInfoV2 {
+            signature: &Default::default(),
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &transaction_status_meta,
+            index: 0,
+        };
+
+        let mut instructions = cpi_tree(&ReplicaTransactionInfoVersions::V0_0_2(&transaction_info));
+        set_log_ranges(&mut instructions, &[
+            format!("Program {} invoke [1]", a),
+            format!("Program {} failed: custom program error: 0x1", a),
+            format!("Program {} invoke [1]", payer),
+        ]);
+        let json = serde_json::to_value(&instructions).unwrap();
+        assert_eq!(json[0]["logRange"], json!([0, 2]));
+        assert!(json[1].get("logRange").is_none());
+    }
+}
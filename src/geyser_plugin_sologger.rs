@@ -1,17 +1,14 @@
-use std::{env, fs::File, io::Read, thread};
-use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::thread;
+use std::sync::{Arc, Mutex, RwLock};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread::JoinHandle;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use anyhow::Result as AnyResult;
-use crossbeam_deque::{Injector, Steal, Worker};
-use log::{error, info, trace};
-use agave_geyser_plugin_interface::geyser_plugin_interface::{ReplicaAccountInfoV2, ReplicaTransactionInfoV2};
-use solana_transaction_status::option_serializer::OptionSerializer;
+use log::{debug, error, info, trace, warn};
+use serde_json::json;
+use solana_sdk::clock::UnixTimestamp;
 use sologger_log_context::programs_selector::ProgramsSelector;
-use sologger_log_context::sologger_log_context::LogContext;
 
 use {
     agave_geyser_plugin_interface::geyser_plugin_interface::{
@@ -24,39 +21,249 @@ use {
     },
 };
 
+use crate::account_events::{is_account_selected, AccountUpdate};
+use crate::accounts_selector::AccountsSelector;
+use crate::block_events::{emit_block_event, BlockEvent, BlockRollups, RollupGuard};
+use crate::block_time_cache::BlockTimeCache;
+use crate::commitment_buffer::{Commitment, CommitmentBuffer};
 use crate::config_loader;
-use crate::geyser_plugin_sologger_config::GeyserPluginSologgerConfig;
-use crate::inner_transaction::ReplicaTransactionInfo;
+use crate::config_loader::LoadedConfig;
+use crate::cpi_tree::{cpi_tree, emit_cpi_tree};
+use crate::config_watcher::{config_diff, requires_restart, spawn_config_watcher};
+use crate::idl::IdlRegistry;
+use crate::inner_transaction::{is_transaction_selected, is_vote_transaction, ReplicaTransactionInfo, TransactionFields};
 use crate::log_processor::{from_rpc_response, log_contexts_from_logs};
-use crate::logger_lib::init_logger;
+use crate::logger_lib::{apply_sink_filters, init_logger, TransportRuntime};
+use crate::slot_events::{SlotStatusEvent, SlotTracker};
 use crate::sologger_config::SologgerConfig;
+use crate::task_queue::{QueuedTask, TaskQueue, DEFAULT_MAX_QUEUED_TASKS};
 
-pub struct PluginContext {
-    pub(crate) programs_selector: ProgramsSelector,
-    pub(crate) injector: Arc<Injector<Task>>,
+/// How long idle worker threads wait for a task before checking whether they should stop, and releasing the tasks that timed out waiting for a block time
+const WORKER_STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long the drain on unload waits for a task before checking whether the queue is empty
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The parts of the sologger config used while the plugin is running. The whole struct is swapped when the config is reloaded.
+#[derive(Default)]
+pub struct RuntimeConfig {
     pub sologger_config: SologgerConfig,
+    pub(crate) programs_selector: ProgramsSelector,
+    pub(crate) program_names: HashMap<String, String>,
+    pub(crate) accounts_selector: AccountsSelector,
+    pub(crate) commitment: Commitment,
+    pub(crate) idls: IdlRegistry,
+}
+
+impl From<LoadedConfig> for RuntimeConfig {
+    fn from(loaded_config: LoadedConfig) -> Self {
+        let program_names = loaded_config.sologger_config.programs_selector
+            .as_ref()
+            .map(|selector| selector.program_names())
+            .unwrap_or_default();
+        RuntimeConfig {
+            sologger_config: loaded_config.sologger_config,
+            programs_selector: loaded_config.programs_selector,
+            program_names,
+            accounts_selector: loaded_config.accounts_selector,
+            commitment: loaded_config.commitment,
+            idls: loaded_config.idls,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PluginContext {
+    pub(crate) config: Arc<RwLock<Arc<RuntimeConfig>>>,
+    pub(crate) queue: Arc<TaskQueue<Task>>,
+    pub(crate) block_times: Arc<BlockTimeCache<Task>>,
+    pub(crate) uncommitted: Arc<CommitmentBuffer<Task>>,
+    pub(crate) slots: Arc<SlotTracker>,
+    pub(crate) rollups: Arc<BlockRollups>,
     pub running: Arc<AtomicBool>,
-    pub handles: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    pub handles: Arc<Mutex<Vec<ThreadHandle>>>,
+    pub(crate) watcher: Arc<Mutex<Option<ThreadHandle>>>,
+    /// The runtimes of the log transports, shut down when the plugin is unloaded
+    pub(crate) transport_runtime: Arc<Mutex<TransportRuntime>>,
+}
+
+/// A background thread and the flag used to stop it individually
+pub struct ThreadHandle {
+    running: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
 }
 
 impl PluginContext {
     pub(crate) fn default() -> PluginContext {
         PluginContext {
-            programs_selector: ProgramsSelector::default(),
-            injector: Arc::new(Default::default()),
-            sologger_config: SologgerConfig::default(),
+            config: Arc::new(RwLock::new(Arc::new(RuntimeConfig::default()))),
+            queue: Arc::new(TaskQueue::default()),
+            block_times: Arc::new(BlockTimeCache::default()),
+            uncommitted: Arc::new(CommitmentBuffer::default()),
+            slots: Arc::new(SlotTracker::default()),
+            rollups: Arc::new(BlockRollups::default()),
             running: Arc::new(Default::default()),
-            handles: Arc::new(Mutex::new(Vec::new()))
+            handles: Arc::new(Mutex::new(Vec::new())),
+            watcher: Arc::new(Mutex::new(None)),
+            transport_runtime: Arc::new(Mutex::new(TransportRuntime::default())),
+        }
+    }
+
+    /// Returns the current config. The returned config is not affected by later reloads.
+    pub fn config(&self) -> Arc<RuntimeConfig> {
+        Arc::clone(&self.config.read().unwrap())
+    }
+
+    /// Swaps in a newly loaded config, applies the filters of the transports, releases the held tasks that reached a new commitmentLevel, and resizes the worker pool if needed.
+    /// Emits a single reloaded event with the applied changes and the new effective config. The changed fields that are only read when the plugin is loaded
+    /// are listed under `requiresRestart` instead, and the event is then logged as a warning.
+    pub(crate) fn apply_config(&self, loaded_config: LoadedConfig) {
+        let new_config = Arc::new(RuntimeConfig::from(loaded_config));
+        let old_config = std::mem::replace(&mut *self.config.write().unwrap(), Arc::clone(&new_config));
+
+        let requires_restart = requires_restart(&old_config.sologger_config, &new_config.sologger_config);
+        let diff = config_diff(&old_config.sologger_config, &new_config.sologger_config, &requires_restart);
+        let mut event = json!({
+            "event": "reloaded",
+            "changes": diff,
+            "config": &new_config.sologger_config,
+        });
+        if requires_restart.is_empty() {
+            info!(target: "sologger_geyser_plugin::geyser_plugin_sologger", "{}", event);
+        } else {
+            event["requiresRestart"] = json!(requires_restart);
+            warn!(target: "sologger_geyser_plugin::geyser_plugin_sologger", "{}", event);
+        }
+        new_config.idls.warn_unsupported();
+        apply_sink_filters(&new_config.sologger_config.transport());
+        if new_config.commitment != old_config.commitment {
+            for task in self.uncommitted.release_committed(new_config.commitment) {
+                self.enqueue(task);
+            }
+        }
+        self.queue.set_overflow_policy(new_config.sologger_config.queue_overflow_policy);
+        self.set_worker_count(new_config.sologger_config.log_processor_worker_thread_count as usize);
+    }
+
+    /// Starts or stops worker threads until `count` workers are running
+    pub(crate) fn set_worker_count(&self, count: usize) {
+        let stopped = {
+            let mut handles = self.handles.lock().unwrap();
+            if handles.len() < count {
+                info!("Starting {:?} log processing worker threads...", count - handles.len());
+            }
+            while handles.len() < count {
+                let worker_running = Arc::new(AtomicBool::new(true));
+                let context = self.clone();
+                let running_worker = Arc::clone(&worker_running);
+                let handle = thread::spawn(move || {
+                    SologgerGeyserPlugin::worker_thread(context, running_worker);
+                });
+                handles.push(ThreadHandle { running: worker_running, handle });
+            }
+            let stopped = handles.split_off(count);
+            if !stopped.is_empty() {
+                info!("Stopping {:?} log processing worker threads...", stopped.len());
+            }
+            stopped
+        };
+        for worker in &stopped {
+            worker.running.store(false, Ordering::SeqCst);
+        }
+        for worker in stopped {
+            worker.handle.join().unwrap();
+        }
+    }
+
+    /// Holds the task until its slot reaches the configured commitment, then queues it
+    pub(crate) fn submit(&self, task: Task) {
+        let (slot, commitment) = (task.slot, task.config.commitment);
+        if let Some(task) = self.uncommitted.hold(slot, task, commitment) {
+            self.enqueue(task);
+        }
+    }
+
+    /// Records the new status of the slot and queues the tasks of the slots that reached the configured commitment.
+    /// The tasks of abandoned slots are queued tagged as orphaned if emitOrphanedLogs is set, and discarded otherwise.
+    pub(crate) fn update_commitment(&self, slot: u64, parent: Option<u64>, status: Commitment) {
+        let config = self.config();
+        let released = self.uncommitted.update(slot, parent, status, config.commitment);
+        for task in released.committed {
+            self.enqueue(task);
+        }
+        if released.orphaned.is_empty() {
+            return;
+        }
+        if config.sologger_config.emit_orphaned_logs {
+            for task in released.orphaned {
+                self.enqueue(task.into_orphaned());
+            }
+        } else {
+            debug!("Discarded {} transactions from slots abandoned before root {}", released.orphaned.len(), slot);
+        }
+    }
+
+    /// Queues the task for the worker threads once the block time of its slot is known.
+    /// The task is held back until the block metadata of the slot is notified, for at most blockTimeTimeoutMillis.
+    pub(crate) fn enqueue(&self, task: Task) {
+        let timeout = Duration::from_millis(task.config.sologger_config.block_time_timeout_millis);
+        if let Some((task, block_time)) = self.block_times.resolve(task.slot, task, timeout) {
+            self.queue.push(task.with_block_time(block_time));
+        }
+        self.release_expired();
+    }
+
+    /// Records the block time of the slot and queues the tasks that were waiting for it
+    pub(crate) fn set_block_time(&self, slot: u64, block_time: Option<UnixTimestamp>) {
+        for task in self.block_times.insert(slot, block_time) {
+            self.queue.push(task.with_block_time(block_time));
+        }
+        self.release_expired();
+    }
+
+    /// Queues the tasks that timed out waiting for the block metadata of their slot, without a block time
+    fn release_expired(&self) {
+        for task in self.block_times.take_expired(Instant::now()) {
+            self.queue.push(task);
+        }
+    }
+
+    /// Helps the worker threads process the queued tasks until the queue is empty or `timeout` has passed.
+    /// Returns the number of tasks left in the queue, which are discarded.
+    pub(crate) fn drain(&self, timeout: Duration) -> usize {
+        let deadline = Instant::now() + timeout;
+        for task in self.block_times.take_all() {
+            self.queue.push(task);
+        }
+        while Instant::now() < deadline {
+            match self.queue.pop_timeout(DRAIN_POLL_INTERVAL.min(deadline.saturating_duration_since(Instant::now()))) {
+                Some(task) => SologgerGeyserPlugin::process_task(task),
+                None if self.queue.is_empty() => break,
+                None => {}
+            }
+        }
+        let discarded = self.queue.len();
+        while self.queue.pop_timeout(Duration::ZERO).is_some() {}
+        discarded
+    }
+
+    /// Stops the config watcher thread, if one is running
+    pub(crate) fn stop_watcher(&self) {
+        if let Some(watcher) = self.watcher.lock().unwrap().take() {
+            watcher.running.store(false, Ordering::SeqCst);
+            watcher.handle.thread().unpark();
+            watcher.handle.join().unwrap();
         }
     }
 
     pub fn join_threads(&self) {
+        self.stop_watcher();
         let handles = {
             let mut handles = self.handles.lock().unwrap();
             std::mem::take(&mut *handles) // empty the Vec inside the Mutex
         };
-        for handle in handles {
-            handle.join().unwrap();
+        for worker in handles {
+            worker.handle.join().unwrap();
         }
     }
 }
@@ -69,7 +276,34 @@ pub struct SologgerGeyserPlugin {
 pub struct Task {
     slot: u64,
     transaction_info: Option<ReplicaTransactionInfo>,
-    programs_select: ProgramsSelector,
+    /// An account update, whose data is decoded with the IDL of its owner program by the worker threads
+    account_update: Option<AccountUpdate>,
+    /// The config at the time the transaction was notified, shared by all tasks until the config is reloaded
+    config: Arc<RuntimeConfig>,
+    /// Holds back the block event of the slot until the task is done, if blockProgramRollup is enabled
+    rollup: Option<RollupGuard>,
+}
+
+impl Task {
+    fn into_orphaned(mut self) -> Self {
+        if let Some(transaction_info) = self.transaction_info.as_mut() {
+            transaction_info.orphaned = true;
+        }
+        self
+    }
+
+    fn with_block_time(mut self, block_time: Option<UnixTimestamp>) -> Self {
+        if let Some(transaction_info) = self.transaction_info.as_mut() {
+            transaction_info.block_time = block_time;
+        }
+        self
+    }
+}
+
+impl QueuedTask for Task {
+    fn has_errors(&self) -> bool {
+        self.transaction_info.as_ref().is_some_and(|transaction_info| transaction_info.transaction_error.is_some())
+    }
 }
 
 /// Implementation of GeyserPlugin trait/interface
@@ -81,82 +315,114 @@ impl GeyserPlugin for SologgerGeyserPlugin {
     
     /// Lifecycle: the plugin has been loaded by the system
     /// used for doing whatever initialization is required by the plugin.
-    /// The _config_file contains the name of the
+    /// The config_file contains the name of the
     /// of the config file. The config must be in JSON format and
     /// include a field "libpath" indicating the full path
     /// name of the shared library implementing this interface.
-    fn on_load(&mut self, _config_file: &str, _is_reload: bool) -> agave_geyser_plugin_interface::geyser_plugin_interface::Result<()> {
-        let (sologger_config, program_selector) = config_loader::load_config().expect("Error loading sologger config");
-        let config = sologger_config.clone();
-        self.context.sologger_config = sologger_config;
-        self.context.programs_selector = program_selector;
+    /// The sologger config is read from the `sologger` or `sologgerConfigPath` fields of this file, see [crate::geyser_plugin_sologger_config::GeyserPluginSologgerConfig].
+    fn on_load(&mut self, config_file: &str, is_reload: bool) -> agave_geyser_plugin_interface::geyser_plugin_interface::Result<()> {
+        let loaded_config = config_loader::load_config(config_file).map_err(|err| {
+            error!("Error loading sologger config: {}", err);
+            GeyserPluginError::from(err)
+        })?;
+        let sologger_config_file = loaded_config.file.clone();
+        let config = loaded_config.sologger_config.clone();
 
-        let _logger = init_logger(&config);
+        if self.context.running.load(Ordering::SeqCst) {
+            // on_load was called again on this running instance: the logger is already installed and the queued tasks are kept, only the workers and the watcher are restarted
+            info!("Reloading sologger-geyser-plugin");
+            self.context.stop_watcher();
+            self.context.set_worker_count(0);
+            self.context.apply_config(loaded_config);
+        } else {
+            // The validator reloads the plugin by unloading it and calling on_load with is_reload on a new instance, which starts like a first load
+            if is_reload {
+                info!("Reloading sologger-geyser-plugin");
+            }
+            *self.context.config.write().unwrap() = Arc::new(RuntimeConfig::from(loaded_config));
 
-        info!("Programs Selected: {:?}", &self.context.programs_selector);
+            *self.context.transport_runtime.lock().unwrap() = init_logger(&config).map_err(|err| {
+                error!("Error initializing the logger: {}", err);
+                GeyserPluginError::from(err)
+            })?;
 
-        // Create an atomic flag for shutdown signal
-        let running = Arc::new(AtomicBool::new(true));
-        let running_clone = Arc::clone(&running);
-        self.context.running = Arc::clone(&running_clone);
+            info!("Programs Selected: {:?}", &self.context.config().programs_selector);
+            self.context.config().idls.warn_unsupported();
+            if config.account_data_notifications_enabled && !self.context.config().accounts_selector.is_enabled() {
+                warn!("accountDataNotificationsEnabled is set, but no accounts are selected by the accountsSelector");
+            }
 
-        // Create an injector for task management
-        self.context.injector = Arc::new(Injector::new());
+            // Create an atomic flag for shutdown signal
+            self.context.running = Arc::new(AtomicBool::new(true));
 
-        // Start worker threads for processing tasks
-        let thread_count = config.log_processor_worker_thread_count as usize;
-        info!("Starting {:?} log processing worker threads...", &thread_count);
-        for _ in 0..thread_count {
-            let injector_clone = Arc::clone(&self.context.injector);
-            let running_worker = Arc::clone(&running_clone);
-            let handle = thread::spawn(move || {
-                Self::worker_thread(injector_clone, running_worker);
-            });
-            self.context.handles.lock().unwrap().push(handle);
+            // Create a bounded queue for task management
+            let max_queued_tasks = config.max_queued_tasks.unwrap_or(DEFAULT_MAX_QUEUED_TASKS);
+            self.context.queue = Arc::new(TaskQueue::new(max_queued_tasks, config.queue_overflow_policy));
+            info!("Task queue capacity: {}", self.context.queue.capacity());
+
+            // Start worker threads for processing tasks
+            self.context.set_worker_count(config.log_processor_worker_thread_count as usize);
+
+            info!("sologger-geyser-plugin loaded");
         }
 
-        info!("sologger-geyser-plugin loaded");
+        if config.config_reload_interval_secs > 0 {
+            let watcher_running = Arc::new(AtomicBool::new(true));
+            let handle = spawn_config_watcher(
+                self.context.clone(),
+                Arc::clone(&watcher_running),
+                config_file.to_string(),
+                sologger_config_file,
+                Duration::from_secs(config.config_reload_interval_secs),
+            );
+            *self.context.watcher.lock().unwrap() = Some(ThreadHandle { running: watcher_running, handle });
+        }
 
         Ok(())
     }
 
     /// Lifecycle: the plugin will be unloaded by the plugin manager
     /// Note: Do any cleanup necessary.
+    /// Queued transactions are processed until the queue is empty or the drainTimeoutMillis deadline is hit.
     fn on_unload(&mut self) {
-        info!("[on_unload] - Flushing logger");
-        let _ = &self.context.running.store(false, Ordering::SeqCst);
+        let drain_timeout = Duration::from_millis(self.context.config().sologger_config.drain_timeout_millis);
+        info!("[on_unload] - Draining {} queued tasks", self.context.queue.len());
+        self.context.stop_watcher();
+        let discarded = self.context.drain(drain_timeout);
+        let uncommitted = self.context.uncommitted.clear();
+
+        self.context.running.store(false, Ordering::SeqCst);
         self.context.join_threads();
+        if discarded > 0 {
+            warn!("[on_unload] - Drain deadline of {:?} reached, discarded {} queued tasks", drain_timeout, discarded);
+        }
+        if uncommitted > 0 {
+            info!("[on_unload] - Discarded {} transactions whose slot did not reach the commitment level", uncommitted);
+        }
+        if self.context.queue.dropped() > 0 {
+            info!("[on_unload] - Dropped {} tasks ({} with errors) because the task queue was full",
+                self.context.queue.dropped(), self.context.queue.dropped_errors());
+        }
+        info!("[on_unload] - Flushing logger");
         log::logger().flush();
+        self.context.transport_runtime.lock().unwrap().shutdown();
     }
 
     /// Event: an account has been updated at slot
     /// - When `is_startup` is true, it indicates the account is loaded from
-    /// snapshots when the validator starts up.
+    ///   snapshots when the validator starts up.
     /// - When `is_startup` is false, the account is updated during transaction processing.
+    ///
     /// Note: The account is versioned, so you can decide how to handle the different
     /// implementations.
-    fn update_account(&self, account: ReplicaAccountInfoVersions, slot: u64, _is_startup: bool) -> agave_geyser_plugin_interface::geyser_plugin_interface::Result<()> {
-        match account {
-            ReplicaAccountInfoVersions::V0_0_1(_) => {
-                return Err(GeyserPluginError::AccountsUpdateError { msg: "ReplicaAccountInfoVersions::V0_0_1 it not supported".to_string() });
-            }
-            ReplicaAccountInfoVersions::V0_0_2(account) => {
-                let _acc = format!(
-                    "V0_0_2 pubkey: {}, owner: {}",
-                    bs58::encode(account.pubkey).into_string(),
-                    bs58::encode(account.owner).into_string(),
-                );
-                info!(target: "sologger_geyser_plugin::geyser_plugin_sologger::update_account", "[update_account] - account: {:#?}, slot:{:#?}, is_startup:{:#?}", _acc, slot, _is_startup);
-            }
-            ReplicaAccountInfoVersions::V0_0_3(account) => {
-                let _acc = format!(
-                    "Updated V0_0_3 account pubkey: {}, owner: {}",
-                    bs58::encode(account.pubkey).into_string(),
-                    bs58::encode(account.owner).into_string(),
-                );
-                info!(target: "sologger_geyser_plugin::geyser_plugin_sologger::update_account", "[update_account] - account: {:#?}, slot:{:#?}, is_startup:{:#?}", _acc, slot, _is_startup);
-            }
+    fn update_account(&self, account: ReplicaAccountInfoVersions, slot: u64, is_startup: bool) -> agave_geyser_plugin_interface::geyser_plugin_interface::Result<()> {
+        let config = self.context.config();
+        if !is_account_selected(&account, &config.accounts_selector) {
+            return Ok(());
         }
+        // Account updates are not held for a commitment or block time, since they are not tied to the transaction logs
+        let account_update = AccountUpdate::new(&account, slot, is_startup, &config.idls);
+        self.context.queue.push(Task { slot, transaction_info: None, account_update: Some(account_update), config, rollup: None });
         Ok(())
     }
 
@@ -168,96 +434,118 @@ impl GeyserPlugin for SologgerGeyserPlugin {
     }
 
     // Event: a slot status is updated.
-    fn update_slot_status(&self, _slot: u64, _parent: Option<u64>, _status: SlotStatus) -> agave_geyser_plugin_interface::geyser_plugin_interface::Result<()> {
-        info!(target: "sologger_geyser_plugin::geyser_plugin_sologger::update_slot_status", "[update_slot_status], slot:{:#?}, parent:{:#?}, status:{:#?}", _slot, _parent, _status);
+    // Transactions held back until their slot reaches the configured commitment are released here.
+    fn update_slot_status(&self, slot: u64, parent: Option<u64>, status: SlotStatus) -> agave_geyser_plugin_interface::geyser_plugin_interface::Result<()> {
+        let since_first_seen = self.context.slots.observe(slot, &status);
+        let events = &self.context.config().sologger_config.slot_status_events;
+        let enabled = match status {
+            SlotStatus::Processed => events.processed,
+            SlotStatus::Confirmed => events.confirmed,
+            SlotStatus::Rooted => events.rooted,
+        };
+        if enabled {
+            let event = SlotStatusEvent::new(slot, parent, &status, since_first_seen);
+            info!(target: "sologger_geyser_plugin::geyser_plugin_sologger::update_slot_status", "{}", json!(event));
+        }
+        self.context.update_commitment(slot, parent, Commitment::from(&status));
         Ok(())
     }
 
     /// Event: a transaction is updated at a slot.
-    #[allow(unused_variables)]
     fn notify_transaction(&self, transaction: ReplicaTransactionInfoVersions, slot: u64) -> agave_geyser_plugin_interface::geyser_plugin_interface::Result<()> {
-        let replication_transaction_info = ReplicaTransactionInfo::from((transaction, slot));
+        let config = self.context.config();
+        if !config.sologger_config.all_with_votes && is_vote_transaction(&transaction) {
+            return Ok(());
+        }
+        if !is_transaction_selected(&transaction, &config.programs_selector) {
+            return Ok(());
+        }
+
+        let fields = TransactionFields::new(&transaction, &config.sologger_config.transaction_fields);
+        let cpi_tree = config.sologger_config.cpi_tree.then(|| cpi_tree(&transaction));
+        let replication_transaction_info = ReplicaTransactionInfo { fields, cpi_tree, ..ReplicaTransactionInfo::from((transaction, slot)) };
         let task = Task {
             slot,
             transaction_info: Some(replication_transaction_info),
-            programs_select: self.context.programs_selector.clone(),
+            account_update: None,
+            rollup: config.sologger_config.block_program_rollup.then(|| self.context.rollups.track(slot)),
+            config,
         };
-        self.context.injector.push(task);
+        self.context.submit(task);
 
         Ok(())
     }
 
     fn notify_block_metadata(&self, blockinfo: ReplicaBlockInfoVersions) -> agave_geyser_plugin_interface::geyser_plugin_interface::Result<()> {
-        match blockinfo {
-            ReplicaBlockInfoVersions::V0_0_1(_blockinfo) => {
-                info!(target: "sologger_geyser_plugin::geyser_plugin_sologger::notify_block_metadata", "[notify_block_metadata], block_info:{:#?}", _blockinfo);
-            }
-            ReplicaBlockInfoVersions::V0_0_2(_blockinfo) => {
-                info!(target: "sologger_geyser_plugin::geyser_plugin_sologger::notify_block_metadata", "[notify_block_metadata], block_info:{:#?}", _blockinfo);
-            }
-            ReplicaBlockInfoVersions::V0_0_3(_blockinfo) => {
-                info!(target: "sologger_geyser_plugin::geyser_plugin_sologger::notify_block_metadata", "[notify_block_metadata], block_info:{:#?}", _blockinfo);
-            }
-            ReplicaBlockInfoVersions::V0_0_4(_blockinfo) => {
-                info!(target: "sologger_geyser_plugin::geyser_plugin_sologger::notify_block_metadata", "[notify_block_metadata], block_info:{:#?}", _blockinfo);
-            }
+        let event = BlockEvent::from(&blockinfo);
+        self.context.set_block_time(event.slot, event.block_time);
+        if self.context.config().sologger_config.block_program_rollup {
+            self.context.rollups.add_block(event);
+        } else {
+            emit_block_event(&event);
         }
         Ok(())
     }
 
     fn account_data_notifications_enabled(&self) -> bool {
         trace!("[account_data_notifications_enabled] - are account data notifications enabled: true");
-        self.context.sologger_config.account_data_notifications_enabled
+        self.context.config().sologger_config.account_data_notifications_enabled
     }
 
     fn transaction_notifications_enabled(&self) -> bool {
         trace!("[transaction_notifications_enabled] - are transaction notifications enabled: true");
-        self.context.sologger_config.transaction_notifications_enabled
+        self.context.config().sologger_config.transaction_notifications_enabled
     }
 }
 
 impl SologgerGeyserPlugin {
-    fn worker_thread(injector: Arc<Injector<Task>>, running: Arc<AtomicBool>) {
-        // Create a worker deque for this thread
-        let worker = Worker::new_fifo();
-        let stealer = worker.stealer();
+    fn worker_thread(context: PluginContext, worker_running: Arc<AtomicBool>) {
         info!("Worker thread started");
 
-        while running.load(Ordering::SeqCst) {
-            if let Some(task) = worker.pop() {
-                Self::process_task(task);
-            } else if let Steal::Success(task) = injector.steal() {
-                Self::process_task(task);
-            } else if let Steal::Success(task) = stealer.steal() {
-                Self::process_task(task);
-            } else {
-                // Sleep for a short duration to avoid busy-waiting
-                thread::sleep(Duration::from_millis(10));
+        while context.running.load(Ordering::SeqCst) && worker_running.load(Ordering::SeqCst) {
+            // Blocks until a task is available, waking up periodically to check for a shutdown.
+            // The tasks waiting for a block time are also released when no notification arrives to release them.
+            match context.queue.pop_timeout(WORKER_STOP_CHECK_INTERVAL) {
+                Some(task) => Self::process_task(task),
+                None => context.release_expired(),
             }
         }
         info!("Worker thread shutting down");
     }
 
     fn process_task(task: Task) {
-        // Process the task (example: log the slot number)
-        if let Some(transaction_info) = task.transaction_info {
+        if let Some(account_update) = task.account_update {
+            let event = account_update.into_event(&task.config.idls);
+            info!(target: "sologger_geyser_plugin::geyser_plugin_sologger::update_account", "{}", json!(event));
+        }
+        if let Some(mut transaction_info) = task.transaction_info {
             trace!("Processing transaction info at slot: {}", task.slot);
-            // Process the transaction info here
-            match transaction_info.meta.log_messages {
-                OptionSerializer::Some(ref log_messages) => {
-                    if log_messages.is_empty() {
-                        return;
+            if transaction_info.log_messages.is_empty() {
+                return;
+            }
+            let log_context_result = from_rpc_response(&transaction_info, &task.config.programs_selector, task.slot);
+            match log_context_result {
+                Ok(log_contexts) => {
+                    if let Some(rollup) = &task.rollup {
+                        rollup.record(&log_contexts);
+                    }
+                    if let Err(err) = log_contexts_from_logs(&log_contexts, &transaction_info, &task.config.program_names, &task.config.idls) {
+                        error!("Error logging the log contexts of transaction {}: {}", transaction_info.signature, err);
                     }
-                    let log_context_result = from_rpc_response(&transaction_info, task.programs_select, task.slot);
-                    match log_context_result {
-                        Ok(log_contexts) => {
-                            log_contexts_from_logs(&log_contexts).expect("Error logging log contexts");
-                        }
-                        Err(_) => { error!("Error occurred logging the log contexts") }
+                    #[cfg(feature = "enable_otel")]
+                    if let Some(metrics) = crate::otel::program_metrics().filter(|_| task.config.sologger_config.metrics) {
+                        crate::log_processor::record_metrics(&log_contexts, &transaction_info, &task.config.program_names, &metrics);
                     }
                 }
-                OptionSerializer::None => {}
-                OptionSerializer::Skip => {}
+                Err(err) => { error!("Error parsing the logs of transaction {}: {}", transaction_info.signature, err) }
+            }
+            if let Some(mut instructions) = transaction_info.cpi_tree.take() {
+                emit_cpi_tree(&transaction_info, &mut instructions);
+            }
+            #[cfg(feature = "enable_otel")]
+            if task.config.sologger_config.traces {
+                let tracer = opentelemetry::global::tracer(crate::transaction_trace::TRACER_NAME);
+                crate::transaction_trace::export_trace(&tracer, &transaction_info, &task.config.program_names);
             }
         }
     }
@@ -272,13 +560,19 @@ impl Debug for SologgerGeyserPlugin {
 
 #[cfg(test)]
 pub(crate) mod tests {
+    use std::str::FromStr;
+    use agave_geyser_plugin_interface::geyser_plugin_interface::{ReplicaBlockInfoV3, ReplicaTransactionInfoV2};
     use solana_transaction_status::TransactionStatusMeta;
 
     use {
         solana_sdk::{
             hash::Hash,
-            signature::{Keypair, Signature, Signer},
+            instruction::{AccountMeta, Instruction},
+            pubkey::Pubkey,
+            signature::{Keypair, Signer},
+            system_program,
             system_transaction,
+            vote::{instruction as vote_instruction, state::Vote},
             transaction::{
                 SanitizedTransaction, SimpleAddressLoader, Transaction, VersionedTransaction,
             },
@@ -293,27 +587,18 @@ pub(crate) mod tests {
         system_transaction::transfer(&keypair1, &pubkey1, 42, zero)
     }
 
-    #[test]
-    fn notify_transaction_test() {
-        solana_logger::setup_with_default("info");
-
-        let mut logs: Vec<String> = vec![];
-        logs.push("Program 9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7 invoke [1]".to_string());
-        logs.push("Program log: Instruction: Initialize".to_string());
-        logs.push("Program 11111111111111111111111111111111 invoke [2]".to_string());
-        logs.push("Program 11111111111111111111111111111111 success".to_string());
-        logs.push("Program log: Initialized new event. Current value".to_string());
-        logs.push("Program 9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7 consumed 59783 of 200000 compute units".to_string());
-        logs.push("Program 9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7 success".to_string());
-
-        let signature = Signature::from([1u8; 64]);
-
-        let message_hash = Hash::new_unique();
-        let transaction = build_test_transaction_legacy();
+    fn build_test_program_transaction(program_id: &str) -> Transaction {
+        let payer = Keypair::new();
+        let instruction = Instruction::new_with_bytes(Pubkey::from_str(program_id).unwrap(), &[0], vec![
+            AccountMeta::new(payer.pubkey(), true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ]);
+        Transaction::new_signed_with_payer(&[instruction], Some(&payer.pubkey()), &[&payer], Hash::default())
+    }
 
+    fn notify_test_transaction(plugin: &SologgerGeyserPlugin, transaction: Transaction, logs: Vec<String>) {
         let transaction = VersionedTransaction::from(transaction);
-
-        let transaction = SanitizedTransaction::try_create(transaction, message_hash, Some(true), SimpleAddressLoader::Disabled, &Default::default())
+        let transaction = SanitizedTransaction::try_create(transaction, Hash::new_unique(), Some(false), SimpleAddressLoader::Disabled, &Default::default())
             .unwrap();
 
         let transaction_status_meta = TransactionStatusMeta {
@@ -332,25 +617,473 @@ pub(crate) mod tests {
         };
 
         let transaction_info_v2 = ReplicaTransactionInfoV2 {
-            signature: &Default::default(),
+            signature: transaction.signature(),
             is_vote: false,
             transaction: &(transaction),
             transaction_status_meta: &transaction_status_meta,
             index: 0,
         };
 
+        let _ = SologgerGeyserPlugin::notify_transaction(plugin, ReplicaTransactionInfoVersions::V0_0_2(&transaction_info_v2), 1u64);
+    }
+
+    #[test]
+    fn notify_transaction_test() {
+        solana_logger::setup_with_default("info");
+
+        let logs: Vec<String> = vec![
+            "Program 9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7 invoke [1]".to_string(),
+            "Program log: Instruction: Initialize".to_string(),
+            "Program 11111111111111111111111111111111 invoke [2]".to_string(),
+            "Program 11111111111111111111111111111111 success".to_string(),
+            "Program log: Initialized new event. Current value".to_string(),
+            "Program 9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7 consumed 59783 of 200000 compute units".to_string(),
+            "Program 9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7 success".to_string(),
+        ];
+
         let programs_selector = ProgramsSelector::new(&["9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7".to_string()]);
-        let mut geyser_logstash_plugin = SologgerGeyserPlugin {
-            context: PluginContext {
-                programs_selector,
-                injector: Arc::new(Default::default()),
-                sologger_config: Default::default(),
-                running: Arc::new(Default::default()),
-                handles: Arc::new(Mutex::new(vec![])),
+        let geyser_logstash_plugin = SologgerGeyserPlugin {
+            context: PluginContext::default(),
+        };
+        *geyser_logstash_plugin.context.config.write().unwrap() = Arc::new(RuntimeConfig {
+            programs_selector,
+            ..Default::default()
+        });
+
+        let transaction = build_test_program_transaction("9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7");
+        notify_test_transaction(&geyser_logstash_plugin, transaction, logs);
+        assert_eq!(geyser_logstash_plugin.context.queue.len(), 1);
+    }
+
+    #[test]
+    fn notify_unselected_transaction_test() {
+        let logs: Vec<String> = vec![
+            "Program 11111111111111111111111111111111 invoke [1]".to_string(),
+            "Program 11111111111111111111111111111111 success".to_string(),
+        ];
+
+        let geyser_logstash_plugin = SologgerGeyserPlugin {
+            context: PluginContext::default(),
+        };
+        *geyser_logstash_plugin.context.config.write().unwrap() = Arc::new(RuntimeConfig {
+            programs_selector: ProgramsSelector::new(&["9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7".to_string()]),
+            ..Default::default()
+        });
+
+        // The transfer does not touch the selected program
+        notify_test_transaction(&geyser_logstash_plugin, build_test_transaction_legacy(), logs);
+        assert_eq!(geyser_logstash_plugin.context.queue.len(), 0);
+    }
+
+    fn build_test_vote_transaction() -> Transaction {
+        let node_keypair = Keypair::new();
+        let vote_keypair = Keypair::new();
+        let vote = Vote::new(vec![1], Hash::default());
+        let instruction = vote_instruction::vote(&vote_keypair.pubkey(), &node_keypair.pubkey(), vote);
+        Transaction::new_signed_with_payer(&[instruction], Some(&node_keypair.pubkey()), &[&node_keypair], Hash::default())
+    }
+
+    #[test]
+    fn notify_vote_transaction_test() {
+        let logs: Vec<String> = vec![
+            "Program Vote111111111111111111111111111111111111111 invoke [1]".to_string(),
+            "Program Vote111111111111111111111111111111111111111 success".to_string(),
+        ];
+
+        let transaction = VersionedTransaction::from(build_test_vote_transaction());
+        let transaction = SanitizedTransaction::try_create(transaction, Hash::new_unique(), Some(true), SimpleAddressLoader::Disabled, &Default::default())
+            .unwrap();
+
+        let transaction_status_meta = TransactionStatusMeta {
+            log_messages: Option::from(logs),
+            ..Default::default()
+        };
+
+        let transaction_info_v2 = ReplicaTransactionInfoV2 {
+            signature: &Default::default(),
+            is_vote: true,
+            transaction: &(transaction),
+            transaction_status_meta: &transaction_status_meta,
+            index: 0,
+        };
+
+        let geyser_logstash_plugin = SologgerGeyserPlugin {
+            context: PluginContext::default(),
+        };
+        *geyser_logstash_plugin.context.config.write().unwrap() = Arc::new(RuntimeConfig {
+            programs_selector: ProgramsSelector::new(&["*".to_string()]),
+            ..Default::default()
+        });
+        let _ = SologgerGeyserPlugin::notify_transaction(&geyser_logstash_plugin, ReplicaTransactionInfoVersions::V0_0_2(&transaction_info_v2), 1u64);
+        assert_eq!(geyser_logstash_plugin.context.queue.len(), 0);
+
+        *geyser_logstash_plugin.context.config.write().unwrap() = Arc::new(RuntimeConfig {
+            sologger_config: SologgerConfig {
+                all_with_votes: true,
+                ..Default::default()
+            },
+            programs_selector: ProgramsSelector::new(&["*".to_string()]),
+            ..Default::default()
+        });
+        let _ = SologgerGeyserPlugin::notify_transaction(&geyser_logstash_plugin, ReplicaTransactionInfoVersions::V0_0_2(&transaction_info_v2), 1u64);
+        assert_eq!(geyser_logstash_plugin.context.queue.len(), 1);
+    }
+
+    #[test]
+    fn notify_block_metadata_test() {
+        let geyser_logstash_plugin = SologgerGeyserPlugin {
+            context: PluginContext::default(),
+        };
+        let config = Arc::new(RuntimeConfig {
+            sologger_config: SologgerConfig {
+                block_time_timeout_millis: 60000,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let transaction_info = ReplicaTransactionInfo {
+            signature: Default::default(),
+            log_messages: vec!["Program 11111111111111111111111111111111 invoke [1]".to_string()],
+            transaction_error: None,
+            slot: 1,
+            block_time: None,
+            orphaned: false,
+            fields: None,
+            cpi_tree: None,
+        };
+        geyser_logstash_plugin.context.enqueue(Task { slot: 1, transaction_info: Some(transaction_info), account_update: None, config, rollup: None });
+        assert_eq!(geyser_logstash_plugin.context.queue.len(), 0);
+
+        let block_info = ReplicaBlockInfoV3 {
+            parent_slot: 0,
+            parent_blockhash: "",
+            slot: 1,
+            blockhash: "",
+            rewards: &[],
+            block_time: Some(1700000000),
+            block_height: Some(1),
+            executed_transaction_count: 1,
+            entry_count: 1,
+        };
+        let _ = SologgerGeyserPlugin::notify_block_metadata(&geyser_logstash_plugin, ReplicaBlockInfoVersions::V0_0_3(&block_info));
+        let task = geyser_logstash_plugin.context.queue.pop_timeout(Duration::ZERO).unwrap();
+        assert_eq!(task.transaction_info.unwrap().block_time, Some(1700000000));
+    }
+
+    #[test]
+    fn release_expired_block_time_test() {
+        let context = PluginContext::default();
+        context.running.store(true, Ordering::SeqCst);
+        context.set_worker_count(1);
+        let config = Arc::new(RuntimeConfig {
+            sologger_config: SologgerConfig { block_time_timeout_millis: 50, ..Default::default() },
+            ..Default::default()
+        });
+        context.enqueue(Task { slot: 1, transaction_info: None, account_update: None, config, rollup: None });
+
+        // No block metadata or transaction is notified after the task, so the idle worker releases it once it times out
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while context.block_times.pending_len() > 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(context.block_times.pending_len(), 0);
+
+        context.running.store(false, Ordering::SeqCst);
+        context.join_threads();
+        assert!(context.queue.is_empty());
+    }
+
+    #[test]
+    fn update_account_test() {
+        use agave_geyser_plugin_interface::geyser_plugin_interface::{ReplicaAccountInfoV2, ReplicaAccountInfoVersions};
+        use crate::idl::tests::{test_counter_data, test_idl};
+
+        let geyser_logstash_plugin = SologgerGeyserPlugin {
+            context: PluginContext::default(),
+        };
+        let (owner, authority) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut idls = IdlRegistry::default();
+        idls.insert(owner, test_idl());
+        *geyser_logstash_plugin.context.config.write().unwrap() = Arc::new(RuntimeConfig {
+            accounts_selector: AccountsSelector::new(&[], &[owner.to_string()]),
+            idls,
+            ..Default::default()
+        });
+        let data = test_counter_data(&authority);
+        let account = ReplicaAccountInfoV2 {
+            pubkey: authority.as_ref(),
+            lamports: 1000,
+            owner: owner.as_ref(),
+            executable: false,
+            rent_epoch: 0,
+            data: &data,
+            write_version: 1,
+            txn_signature: None,
+        };
+        let _ = geyser_logstash_plugin.update_account(ReplicaAccountInfoVersions::V0_0_2(&account), 1, false);
+
+        // The account data is decoded by the worker threads rather than on the validator thread
+        let task = geyser_logstash_plugin.context.queue.pop_timeout(Duration::ZERO).unwrap();
+        let event = serde_json::to_value(task.account_update.unwrap().into_event(&task.config.idls)).unwrap();
+        assert_eq!(event["accountType"], "Counter");
+        assert_eq!(event["data"]["count"], 42);
+    }
+
+    #[test]
+    fn update_slot_status_test() {
+        let geyser_logstash_plugin = SologgerGeyserPlugin {
+            context: PluginContext::default(),
+        };
+        let config = Arc::new(RuntimeConfig {
+            sologger_config: SologgerConfig {
+                emit_orphaned_logs: true,
+                ..Default::default()
             },
+            commitment: Commitment::Confirmed,
+            ..Default::default()
+        });
+        *geyser_logstash_plugin.context.config.write().unwrap() = Arc::clone(&config);
+        let task = |slot: u64| Task {
+            slot,
+            transaction_info: Some(ReplicaTransactionInfo {
+                signature: Default::default(),
+                log_messages: vec!["Program 11111111111111111111111111111111 invoke [1]".to_string()],
+                transaction_error: None,
+                slot,
+                block_time: None,
+                orphaned: false,
+                fields: None,
+                cpi_tree: None,
+            }),
+            account_update: None,
+            config: Arc::clone(&config),
+            rollup: None,
+        };
+
+        // Slots 2 and 3 fork from slot 1
+        let _ = geyser_logstash_plugin.update_slot_status(2, Some(1), SlotStatus::Processed);
+        let _ = geyser_logstash_plugin.update_slot_status(3, Some(1), SlotStatus::Processed);
+        geyser_logstash_plugin.context.submit(task(2));
+        geyser_logstash_plugin.context.submit(task(3));
+        assert_eq!(geyser_logstash_plugin.context.queue.len(), 0);
+
+        let _ = geyser_logstash_plugin.update_slot_status(3, None, SlotStatus::Confirmed);
+        let task = geyser_logstash_plugin.context.queue.pop_timeout(Duration::ZERO).unwrap();
+        assert_eq!(task.slot, 3);
+        assert!(!task.transaction_info.unwrap().orphaned);
+        assert!(geyser_logstash_plugin.context.queue.is_empty());
+
+        let _ = geyser_logstash_plugin.update_slot_status(3, Some(1), SlotStatus::Rooted);
+        let task = geyser_logstash_plugin.context.queue.pop_timeout(Duration::ZERO).unwrap();
+        assert_eq!(task.slot, 2);
+        assert!(task.transaction_info.unwrap().orphaned);
+    }
+
+    #[test]
+    fn apply_config_commitment_test() {
+        let context = PluginContext::default();
+        let config = Arc::new(RuntimeConfig {
+            sologger_config: SologgerConfig { block_time_timeout_millis: 0, ..Default::default() },
+            commitment: Commitment::Rooted,
+            ..Default::default()
+        });
+        *context.config.write().unwrap() = Arc::clone(&config);
+        context.update_commitment(1, Some(0), Commitment::Confirmed);
+        context.submit(Task { slot: 1, transaction_info: None, account_update: None, config: Arc::clone(&config), rollup: None });
+        assert!(context.queue.is_empty());
+
+        // Slot 1 is already confirmed, so its task is released when the commitmentLevel is lowered to confirmed
+        context.apply_config(LoadedConfig {
+            sologger_config: config.sologger_config.clone(),
+            programs_selector: ProgramsSelector::default(),
+            accounts_selector: AccountsSelector::default(),
+            commitment: Commitment::Confirmed,
+            idls: IdlRegistry::default(),
+            file: Default::default(),
+        });
+        assert_eq!(context.queue.pop_timeout(Duration::ZERO).unwrap().slot, 1);
+    }
+
+    #[test]
+    fn apply_config_test() {
+        let context = PluginContext::default();
+        context.running.store(true, Ordering::SeqCst);
+        context.set_worker_count(2);
+        assert_eq!(context.handles.lock().unwrap().len(), 2);
+
+        context.apply_config(LoadedConfig {
+            sologger_config: SologgerConfig { log_processor_worker_thread_count: 1, ..Default::default() },
+            programs_selector: ProgramsSelector::new(&["9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7".to_string()]),
+            accounts_selector: AccountsSelector::default(),
+            commitment: Commitment::Processed,
+            idls: IdlRegistry::default(),
+            file: Default::default(),
+        });
+
+        assert_eq!(context.handles.lock().unwrap().len(), 1);
+        assert!(context.config().programs_selector.is_program_selected_string("9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7"));
+        assert_eq!(context.config().sologger_config.log_processor_worker_thread_count, 1);
+
+        context.running.store(false, Ordering::SeqCst);
+        context.join_threads();
+        assert!(context.handles.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn on_load_reload_test() {
+        let dir = std::env::temp_dir().join("sologger-plugin-tests").join("reload");
+        std::fs::create_dir_all(&dir).unwrap();
+        let config_file = dir.join("plugin-config.json");
+        let write_config = |thread_count: u8| {
+            std::fs::write(&config_file, json!({
+                "libpath": "libsologger_geyser_plugin.so",
+                "sologger": {
+                    "rpcUrl": "wss://api.devnet.solana.com",
+                    "programsSelector": { "programs": ["*"] },
+                    "accountDataNotificationsEnabled": false,
+                    "transactionNotificationsEnabled": true,
+                    "logProcessorWorkerThreadCount": thread_count,
+                    "maxQueuedTasks": 100,
+                    "blockTimeTimeoutMillis": 0,
+                    "blockProgramRollup": true,
+                    "configReloadIntervalSecs": 60,
+                    "transport": {}
+                }
+            }).to_string()).unwrap();
+        };
+        let config_file_path = config_file.to_str().unwrap();
+
+        let mut plugin = SologgerGeyserPlugin { context: PluginContext::default() };
+        write_config(2);
+        plugin.on_load(config_file_path, false).unwrap();
+        assert_eq!(plugin.context.handles.lock().unwrap().len(), 2);
+        plugin.on_unload();
+
+        // The validator unloads the plugin, then calls on_load with is_reload on a new instance
+        let mut plugin = SologgerGeyserPlugin { context: PluginContext::default() };
+        write_config(3);
+        plugin.on_load(config_file_path, true).unwrap();
+        assert!(plugin.context.running.load(Ordering::SeqCst));
+        assert_eq!(plugin.context.handles.lock().unwrap().len(), 3);
+        assert_eq!(plugin.context.queue.capacity(), 100);
+        assert!(plugin.context.watcher.lock().unwrap().is_some());
+
+        let logs = vec![
+            "Program 9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7 invoke [1]".to_string(),
+            "Program 9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7 success".to_string(),
+        ];
+        notify_test_transaction(&plugin, build_test_program_transaction("9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7"), logs);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while plugin.context.rollups.pending_tasks(1) != Some(0) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(plugin.context.rollups.pending_tasks(1), Some(0), "the transaction was not processed by the workers");
+
+        plugin.on_unload();
+        assert!(plugin.context.handles.lock().unwrap().is_empty());
+        assert!(plugin.context.watcher.lock().unwrap().is_none());
+    }
+
+    #[cfg(feature = "enable_otel")]
+    #[test]
+    fn on_load_reload_exports_test() {
+        use opentelemetry_proto::tonic::common::v1::any_value::Value;
+        use opentelemetry_proto::tonic::metrics::v1::metric::Data;
+        use opentelemetry_proto::tonic::trace::v1::Span;
+        use crate::otel::OtelRuntime;
+        use crate::otel::tests::{start_collector, GLOBAL_PROVIDERS};
+
+        let _global_providers = GLOBAL_PROVIDERS.lock().unwrap_or_else(|err| err.into_inner());
+        let collector_runtime = OtelRuntime::start().unwrap();
+        let (endpoint, collector) = start_collector(collector_runtime.handle());
+        let dir = std::env::temp_dir().join("sologger-plugin-tests").join("reload-otel");
+        std::fs::create_dir_all(&dir).unwrap();
+        let otel_config_file = dir.join("opentelemetry-config.json");
+        std::fs::write(&otel_config_file, json!({
+            "logConfig": { "service.name": "sologger-geyser-plugin" },
+            "endpoint": endpoint,
+            "metricsEndpoint": endpoint,
+            "tracesEndpoint": endpoint,
+            "logLevel": "info"
+        }).to_string()).unwrap();
+        let config_file = dir.join("plugin-config.json");
+        std::fs::write(&config_file, json!({
+            "libpath": "libsologger_geyser_plugin.so",
+            "sologger": {
+                "rpcUrl": "wss://api.devnet.solana.com",
+                "opentelemetryConfigLocation": otel_config_file,
+                "programsSelector": { "programs": ["*"] },
+                "accountDataNotificationsEnabled": false,
+                "transactionNotificationsEnabled": true,
+                "logProcessorWorkerThreadCount": 1,
+                "blockTimeTimeoutMillis": 0,
+                "traces": true,
+                "metrics": true,
+                "transport": {}
+            }
+        }).to_string()).unwrap();
+        let config_file_path = config_file.to_str().unwrap();
+
+        let notify_program_transaction = |plugin: &SologgerGeyserPlugin, program_id: &str| {
+            let transaction = build_test_program_transaction(program_id);
+            let signature = transaction.signatures[0];
+            let logs = vec![format!("Program {} invoke [1]", program_id), format!("Program {} success", program_id)];
+            notify_test_transaction(plugin, transaction, logs);
+            signature
         };
-        
-        ReplicaTransactionInfoVersions::V0_0_2(&transaction_info_v2);
-        let _ = SologgerGeyserPlugin::notify_transaction(&mut geyser_logstash_plugin, ReplicaTransactionInfoVersions::V0_0_2(&transaction_info_v2), 1u64);
+
+        let mut plugin = SologgerGeyserPlugin { context: PluginContext::default() };
+        plugin.on_load(config_file_path, false).unwrap();
+        notify_program_transaction(&plugin, "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+        plugin.on_unload();
+
+        // The exporters shut down by on_unload are started again by the new instance
+        let mut plugin = SologgerGeyserPlugin { context: PluginContext::default() };
+        plugin.on_load(config_file_path, true).unwrap();
+        let signature = notify_program_transaction(&plugin, "9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7");
+        // Unloading processes the queued transaction and exports the remaining spans and metrics
+        plugin.on_unload();
+
+        let spans: Vec<Span> = collector.traces
+            .try_iter()
+            .flat_map(|request| request.resource_spans)
+            .flat_map(|resource_spans| resource_spans.scope_spans)
+            .flat_map(|scope_spans| scope_spans.spans)
+            .collect();
+        assert!(spans.iter().any(|span| span.name == "transaction" && span.trace_id == signature.as_ref()[..16]));
+        let invoked_programs: Vec<Option<Value>> = collector.metrics
+            .try_iter()
+            .flat_map(|request| request.resource_metrics)
+            .flat_map(|resource_metrics| resource_metrics.scope_metrics)
+            .flat_map(|scope_metrics| scope_metrics.metrics)
+            .filter(|metric| metric.name == "solana.program.invocations")
+            .filter_map(|metric| match metric.data {
+                Some(Data::Sum(sum)) => Some(sum.data_points),
+                _ => None,
+            })
+            .flatten()
+            .flat_map(|data_point| data_point.attributes)
+            .filter(|attribute| attribute.key == "solana.program_id")
+            .map(|attribute| attribute.value.and_then(|value| value.value))
+            .collect();
+        assert!(invoked_programs.contains(&Some(Value::StringValue("9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7".to_string()))));
+    }
+
+    #[test]
+    fn drain_test() {
+        let context = PluginContext::default();
+        let push_tasks = || {
+            for slot in 0..3 {
+                context.queue.push(Task { slot, transaction_info: None, account_update: None, config: Default::default(), rollup: None });
+            }
+        };
+
+        push_tasks();
+        assert_eq!(context.drain(Duration::from_secs(1)), 0);
+        assert!(context.queue.is_empty());
+
+        push_tasks();
+        assert_eq!(context.drain(Duration::ZERO), 3);
+        assert!(context.queue.is_empty());
     }
 }
@@ -3,8 +3,46 @@ use serde::{
     Serialize
 };
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+/// The geyser plugin config file passed to the validator via `--geyser-plugin-config`. Besides the `libpath` required by the validator, it can point to the sologger config or embed it directly.
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct GeyserPluginSologgerConfig {
-    pub(crate) logstash_config_location: String,
-    pub(crate) log_geyser_functions: bool
-}
\ No newline at end of file
+    /// The location of the sologger-config.json file. Relative paths are resolved against the directory of the geyser plugin config file, the same way the validator resolves `libpath`.
+    #[serde(default)]
+    pub(crate) sologger_config_path: Option<String>,
+    /// An inline sologger config, using the same format as sologger-config.json. Takes precedence over `sologgerConfigPath`.
+    #[serde(default)]
+    pub(crate) sologger: Option<serde_json::Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use crate::geyser_plugin_sologger_config::GeyserPluginSologgerConfig;
+
+    #[test]
+    pub fn test_deserialize_path() {
+        let config = json!(
+            {
+                "libpath": "../target/debug/libsologger_geyser_plugin.so",
+                "sologgerConfigPath": "local/sologger-config.json"
+            }
+        );
+
+        let plugin_config = serde_json::from_value::<GeyserPluginSologgerConfig>(config).unwrap();
+        assert_eq!(plugin_config.sologger_config_path.unwrap(), "local/sologger-config.json");
+        assert_eq!(plugin_config.sologger, None);
+    }
+
+    #[test]
+    pub fn test_deserialize_libpath_only() {
+        let config = json!(
+            {
+                "libpath": "../target/debug/libsologger_geyser_plugin.so"
+            }
+        );
+
+        let plugin_config = serde_json::from_value::<GeyserPluginSologgerConfig>(config).unwrap();
+        assert_eq!(plugin_config, GeyserPluginSologgerConfig::default());
+    }
+}
@@ -0,0 +1,662 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::Deserialize;
+use log::warn;
+use serde_json::{json, Map, Value};
+use solana_sdk::hash::hash;
+use solana_sdk::pubkey::Pubkey;
+
+/// How deeply nested types may be decoded, to protect against recursive type definitions
+const MAX_DEPTH: usize = 32;
+
+/// The Anchor IDLs of the programs configured in `idls`, keyed by program ID
+#[derive(Default, Debug, Clone)]
+pub(crate) struct IdlRegistry {
+    programs: HashMap<Pubkey, Idl>,
+}
+
+impl IdlRegistry {
+    /// Adds the IDL of the program. Its accounts and events that use types that are not supported are skipped when decoding.
+    pub(crate) fn insert(&mut self, program_id: Pubkey, mut idl: Idl) {
+        idl.mark_unsupported();
+        self.programs.insert(program_id, idl);
+    }
+
+    /// Returns true if there is an IDL for the program
+    pub(crate) fn contains(&self, program_id: &[u8]) -> bool {
+        Pubkey::try_from(program_id).is_ok_and(|program_id| self.programs.contains_key(&program_id))
+    }
+
+    /// Logs a warning for each account and event that is not decoded because its IDL uses types that are not supported
+    pub(crate) fn warn_unsupported(&self) {
+        for (program_id, idl) in &self.programs {
+            for name in idl.unsupported() {
+                warn!("The IDL of program {} uses types that are not supported by {}, its data is not decoded", program_id, name);
+            }
+        }
+    }
+
+    /// Decodes the data of an account owned by `owner`. Returns None if there is no IDL for the owner program, or if the IDL type of the account is not supported.
+    pub(crate) fn decode_account(&self, owner: &[u8], data: &[u8]) -> Option<Result<Decoded, String>> {
+        let owner = Pubkey::try_from(owner).ok()?;
+        self.programs.get(&owner)?.decode_account(data)
+    }
+
+    /// Decodes the base64 encoded data of a `Program data:` log emitted by `program_id`. Returns None if there is no IDL for the program, or if the IDL type of the event is not supported.
+    pub(crate) fn decode_event(&self, program_id: &str, data: &str) -> Option<Result<Decoded, String>> {
+        let program_id = Pubkey::from_str(program_id).ok()?;
+        let idl = self.programs.get(&program_id)?;
+        match STANDARD.decode(data) {
+            Ok(data) => idl.decode_event(&data),
+            Err(err) => Some(Err(err.to_string())),
+        }
+    }
+}
+
+/// A value decoded with an IDL, with the name of its type
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Decoded {
+    pub(crate) name: String,
+    pub(crate) value: Value,
+}
+
+/// The parts of an Anchor IDL needed to decode account data and events. Both the current (0.30+) and the legacy IDL formats are supported.
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct Idl {
+    #[serde(default)]
+    accounts: Vec<IdlAccount>,
+    #[serde(default)]
+    events: Vec<IdlEvent>,
+    #[serde(default)]
+    types: Vec<IdlTypeDef>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct IdlAccount {
+    name: String,
+    /// Only set in the current IDL format. Legacy IDLs use the first 8 bytes of sha256("account:<name>").
+    #[serde(default)]
+    discriminator: Option<Vec<u8>>,
+    /// Only set in the legacy IDL format. The current format defines the account type in `types`.
+    #[serde(default, rename = "type")]
+    ty: Option<IdlTypeDefTy>,
+    /// Set by [Idl::mark_unsupported] if the account type uses types that are not supported
+    #[serde(skip)]
+    unsupported: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct IdlEvent {
+    name: String,
+    /// Only set in the current IDL format. Legacy IDLs use the first 8 bytes of sha256("event:<name>").
+    #[serde(default)]
+    discriminator: Option<Vec<u8>>,
+    /// Only set in the legacy IDL format. The current format defines the event type in `types`.
+    #[serde(default)]
+    fields: Option<IdlFields>,
+    /// Set by [Idl::mark_unsupported] if the event type uses types that are not supported
+    #[serde(skip)]
+    unsupported: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct IdlTypeDef {
+    name: String,
+    #[serde(rename = "type")]
+    ty: IdlTypeDefTy,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum IdlTypeDefTy {
+    Struct {
+        #[serde(default)]
+        fields: Option<IdlFields>,
+    },
+    Enum {
+        variants: Vec<IdlEnumVariant>,
+    },
+    Type {
+        alias: IdlType,
+    },
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum IdlFields {
+    Named(Vec<IdlField>),
+    Tuple(Vec<IdlType>),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct IdlField {
+    name: String,
+    #[serde(rename = "type")]
+    ty: IdlType,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct IdlEnumVariant {
+    name: String,
+    #[serde(default)]
+    fields: Option<IdlFields>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "lowercase")]
+enum IdlType {
+    Bool,
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+    U64,
+    I64,
+    F64,
+    U128,
+    I128,
+    Bytes,
+    String,
+    #[serde(alias = "publicKey")]
+    Pubkey,
+    Option(Box<IdlType>),
+    COption(Box<IdlType>),
+    Vec(Box<IdlType>),
+    Array(Box<IdlType>, usize),
+    Defined(IdlDefined),
+    /// A type that cannot be decoded, such as u256, i256 or a generic parameter
+    #[serde(untagged)]
+    Unsupported(Value),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum IdlDefined {
+    Name(String),
+    Object {
+        name: String,
+        #[serde(default)]
+        generics: Vec<Value>,
+    },
+}
+
+impl IdlDefined {
+    fn name(&self) -> &str {
+        match self {
+            IdlDefined::Name(name) | IdlDefined::Object { name, .. } => name,
+        }
+    }
+
+    /// Generic types, and the legacy names of wrapped types such as `Option<State>`, cannot be decoded
+    fn is_supported(&self) -> bool {
+        match self {
+            IdlDefined::Name(name) => !name.contains('<'),
+            IdlDefined::Object { name, generics } => !name.contains('<') && generics.is_empty(),
+        }
+    }
+}
+
+impl Idl {
+    /// Checks that every account and event has a type definition
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if let Some(account) = self.accounts.iter().find(|account| self.account_type(account).is_none()) {
+            return Err(format!("No type definition for account {}", account.name));
+        }
+        if let Some(event) = self.events.iter().find(|event| event.fields.is_none() && self.type_def(&event.name).is_none()) {
+            return Err(format!("No type definition for event {}", event.name));
+        }
+        Ok(())
+    }
+
+    /// Marks the accounts and events whose types use types that are not supported, so they are skipped instead of failing to decode
+    fn mark_unsupported(&mut self) {
+        let unsupported_accounts: Vec<bool> = self.accounts
+            .iter()
+            .map(|account| self.account_type(account).is_some_and(|ty| !self.is_type_def_supported(ty, 0)))
+            .collect();
+        for (account, unsupported) in self.accounts.iter_mut().zip(unsupported_accounts) {
+            account.unsupported = unsupported;
+        }
+        let unsupported_events: Vec<bool> = self.events
+            .iter()
+            .map(|event| match &event.fields {
+                Some(fields) => !self.are_fields_supported(Some(fields), 0),
+                None => self.type_def(&event.name).is_some_and(|ty| !self.is_type_def_supported(ty, 0)),
+            })
+            .collect();
+        for (event, unsupported) in self.events.iter_mut().zip(unsupported_events) {
+            event.unsupported = unsupported;
+        }
+    }
+
+    /// Returns the accounts and events marked by [Idl::mark_unsupported], such as `account Counter`
+    fn unsupported(&self) -> Vec<String> {
+        let accounts = self.accounts.iter().filter(|account| account.unsupported).map(|account| format!("account {}", account.name));
+        let events = self.events.iter().filter(|event| event.unsupported).map(|event| format!("event {}", event.name));
+        accounts.chain(events).collect()
+    }
+
+    /// Decodes account data, using the account whose discriminator prefixes the data. Returns None if the type of the account is not supported.
+    fn decode_account(&self, data: &[u8]) -> Option<Result<Decoded, String>> {
+        let Some((account, discriminator)) = self.accounts
+            .iter()
+            .map(|account| (account, discriminator(account.discriminator.as_deref(), "account", &account.name)))
+            .find(|(_, discriminator)| data.starts_with(discriminator))
+        else {
+            return Some(Err("No account discriminator matches the account data".to_string()));
+        };
+        if account.unsupported {
+            return None;
+        }
+        let decode = || {
+            let ty = self.account_type(account).ok_or_else(|| format!("No type definition for account {}", account.name))?;
+            let mut data = &data[discriminator.len()..];
+            let value = self.decode_type_def(ty, &mut data, 0)?;
+            Ok(Decoded { name: account.name.clone(), value })
+        };
+        Some(decode())
+    }
+
+    /// Decodes event data, using the event whose discriminator prefixes the data. Returns None if the type of the event is not supported.
+    fn decode_event(&self, data: &[u8]) -> Option<Result<Decoded, String>> {
+        let Some((event, discriminator)) = self.events
+            .iter()
+            .map(|event| (event, discriminator(event.discriminator.as_deref(), "event", &event.name)))
+            .find(|(_, discriminator)| data.starts_with(discriminator))
+        else {
+            return Some(Err("No event discriminator matches the event data".to_string()));
+        };
+        if event.unsupported {
+            return None;
+        }
+        let decode = || {
+            let mut data = &data[discriminator.len()..];
+            let value = match &event.fields {
+                Some(fields) => self.decode_fields(Some(fields), &mut data, 0)?,
+                None => {
+                    let ty = self.type_def(&event.name).ok_or_else(|| format!("No type definition for event {}", event.name))?;
+                    self.decode_type_def(ty, &mut data, 0)?
+                }
+            };
+            Ok(Decoded { name: event.name.clone(), value })
+        };
+        Some(decode())
+    }
+
+    fn account_type<'a>(&'a self, account: &'a IdlAccount) -> Option<&'a IdlTypeDefTy> {
+        account.ty.as_ref().or_else(|| self.type_def(&account.name))
+    }
+
+    fn type_def(&self, name: &str) -> Option<&IdlTypeDefTy> {
+        self.types.iter().find(|type_def| type_def.name == name).map(|type_def| &type_def.ty)
+    }
+
+    fn is_type_def_supported(&self, ty: &IdlTypeDefTy, depth: usize) -> bool {
+        match ty {
+            IdlTypeDefTy::Struct { fields } => self.are_fields_supported(fields.as_ref(), depth),
+            IdlTypeDefTy::Enum { variants } => variants.iter().all(|variant| self.are_fields_supported(variant.fields.as_ref(), depth)),
+            IdlTypeDefTy::Type { alias } => self.is_type_supported(alias, depth),
+        }
+    }
+
+    fn are_fields_supported(&self, fields: Option<&IdlFields>, depth: usize) -> bool {
+        match fields {
+            None => true,
+            Some(IdlFields::Named(fields)) => fields.iter().all(|field| self.is_type_supported(&field.ty, depth)),
+            Some(IdlFields::Tuple(types)) => types.iter().all(|ty| self.is_type_supported(ty, depth)),
+        }
+    }
+
+    /// Unknown and too deeply nested types are left to fail when decoding
+    fn is_type_supported(&self, ty: &IdlType, depth: usize) -> bool {
+        if depth > MAX_DEPTH {
+            return true;
+        }
+        let depth = depth + 1;
+        match ty {
+            IdlType::Unsupported(_) => false,
+            IdlType::Option(ty) | IdlType::COption(ty) | IdlType::Vec(ty) | IdlType::Array(ty, _) => self.is_type_supported(ty, depth),
+            IdlType::Defined(defined) => defined.is_supported() && match self.type_def(defined.name()) {
+                Some(type_def) => self.is_type_def_supported(type_def, depth),
+                None => true,
+            },
+            _ => true,
+        }
+    }
+
+    fn decode_type_def(&self, ty: &IdlTypeDefTy, data: &mut &[u8], depth: usize) -> Result<Value, String> {
+        match ty {
+            IdlTypeDefTy::Struct { fields } => self.decode_fields(fields.as_ref(), data, depth),
+            IdlTypeDefTy::Enum { variants } => {
+                let index = take(data, 1)?[0] as usize;
+                let variant = variants.get(index).ok_or_else(|| format!("Invalid enum variant index {}", index))?;
+                match &variant.fields {
+                    None => Ok(Value::String(variant.name.clone())),
+                    Some(fields) => {
+                        let mut value = Map::new();
+                        value.insert(variant.name.clone(), self.decode_fields(Some(fields), data, depth)?);
+                        Ok(Value::Object(value))
+                    }
+                }
+            }
+            IdlTypeDefTy::Type { alias } => self.decode_type(alias, data, depth),
+        }
+    }
+
+    fn decode_fields(&self, fields: Option<&IdlFields>, data: &mut &[u8], depth: usize) -> Result<Value, String> {
+        match fields {
+            None => Ok(Value::Object(Map::new())),
+            Some(IdlFields::Named(fields)) => {
+                let mut values = Map::new();
+                for field in fields {
+                    values.insert(field.name.clone(), self.decode_type(&field.ty, data, depth)?);
+                }
+                Ok(Value::Object(values))
+            }
+            Some(IdlFields::Tuple(types)) => types
+                .iter()
+                .map(|ty| self.decode_type(ty, data, depth))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array),
+        }
+    }
+
+    fn decode_type(&self, ty: &IdlType, data: &mut &[u8], depth: usize) -> Result<Value, String> {
+        if depth > MAX_DEPTH {
+            return Err("Type nesting is too deep".to_string());
+        }
+        let depth = depth + 1;
+        Ok(match ty {
+            IdlType::Bool => Value::Bool(take(data, 1)?[0] != 0),
+            IdlType::U8 => json!(take(data, 1)?[0]),
+            IdlType::I8 => json!(take(data, 1)?[0] as i8),
+            IdlType::U16 => json!(u16::from_le_bytes(take_array(data)?)),
+            IdlType::I16 => json!(i16::from_le_bytes(take_array(data)?)),
+            IdlType::U32 => json!(u32::from_le_bytes(take_array(data)?)),
+            IdlType::I32 => json!(i32::from_le_bytes(take_array(data)?)),
+            IdlType::F32 => json!(f32::from_le_bytes(take_array(data)?)),
+            IdlType::U64 => json!(u64::from_le_bytes(take_array(data)?)),
+            IdlType::I64 => json!(i64::from_le_bytes(take_array(data)?)),
+            IdlType::F64 => json!(f64::from_le_bytes(take_array(data)?)),
+            // 128 bit integers do not fit in a JSON number
+            IdlType::U128 => json!(u128::from_le_bytes(take_array(data)?).to_string()),
+            IdlType::I128 => json!(i128::from_le_bytes(take_array(data)?).to_string()),
+            IdlType::Bytes => json!(STANDARD.encode(take_len_prefixed(data)?)),
+            IdlType::String => json!(String::from_utf8(take_len_prefixed(data)?.to_vec()).map_err(|err| err.to_string())?),
+            IdlType::Pubkey => json!(Pubkey::new_from_array(take_array(data)?).to_string()),
+            IdlType::Option(ty) => match take(data, 1)?[0] {
+                0 => Value::Null,
+                _ => self.decode_type(ty, data, depth)?,
+            },
+            IdlType::COption(ty) => match u32::from_le_bytes(take_array(data)?) {
+                0 => {
+                    // A COption always takes the space of its value
+                    self.decode_type(ty, data, depth)?;
+                    Value::Null
+                }
+                _ => self.decode_type(ty, data, depth)?,
+            },
+            IdlType::Vec(ty) => {
+                let len = u32::from_le_bytes(take_array(data)?) as usize;
+                if len > data.len() {
+                    return Err(format!("Invalid vec length {}", len));
+                }
+                Value::Array((0..len).map(|_| self.decode_type(ty, data, depth)).collect::<Result<_, _>>()?)
+            }
+            IdlType::Array(ty, len) => {
+                Value::Array((0..*len).map(|_| self.decode_type(ty, data, depth)).collect::<Result<_, _>>()?)
+            }
+            IdlType::Defined(defined) => {
+                let type_def = self.type_def(defined.name()).ok_or_else(|| format!("Unknown type {}", defined.name()))?;
+                self.decode_type_def(type_def, data, depth)?
+            }
+            IdlType::Unsupported(ty) => return Err(format!("Unsupported type {}", ty)),
+        })
+    }
+}
+
+/// Returns the discriminator from the IDL, or the Anchor default of the first 8 bytes of sha256("<namespace>:<name>")
+fn discriminator(discriminator: Option<&[u8]>, namespace: &str, name: &str) -> Vec<u8> {
+    match discriminator {
+        Some(discriminator) => discriminator.to_vec(),
+        None => hash(format!("{}:{}", namespace, name).as_bytes()).to_bytes()[..8].to_vec(),
+    }
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], String> {
+    if data.len() < len {
+        return Err("Unexpected end of data".to_string());
+    }
+    let (value, rest) = data.split_at(len);
+    *data = rest;
+    Ok(value)
+}
+
+fn take_array<const N: usize>(data: &mut &[u8]) -> Result<[u8; N], String> {
+    Ok(take(data, N)?.try_into().unwrap())
+}
+
+fn take_len_prefixed<'a>(data: &mut &'a [u8]) -> Result<&'a [u8], String> {
+    let len = u32::from_le_bytes(take_array(data)?) as usize;
+    take(data, len)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use serde_json::json;
+    use solana_sdk::hash::hash;
+    use solana_sdk::pubkey::Pubkey;
+    use crate::idl::{Decoded, Idl, IdlRegistry};
+
+    /// An IDL in the current Anchor format, with an explicit discriminator
+    pub(crate) fn test_idl() -> Idl {
+        serde_json::from_value(json!({
+            "address": "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin",
+            "metadata": { "name": "counter", "version": "0.1.0", "spec": "0.1.0" },
+            "instructions": [],
+            "accounts": [{ "name": "Counter", "discriminator": [1, 2, 3, 4, 5, 6, 7, 8] }],
+            "events": [{ "name": "Incremented", "discriminator": [8, 7, 6, 5, 4, 3, 2, 1] }],
+            "types": [
+                {
+                    "name": "Counter",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            { "name": "authority", "type": "pubkey" },
+                            { "name": "count", "type": "u64" },
+                            { "name": "label", "type": "string" },
+                            { "name": "state", "type": { "defined": { "name": "State" } } },
+                            { "name": "limit", "type": { "option": "u16" } },
+                            { "name": "history", "type": { "vec": "i8" } },
+                            { "name": "total", "type": "u128" }
+                        ]
+                    }
+                },
+                {
+                    "name": "Incremented",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [{ "name": "count", "type": "u64" }, { "name": "state", "type": { "defined": "State" } }]
+                    }
+                },
+                {
+                    "name": "State",
+                    "type": {
+                        "kind": "enum",
+                        "variants": [{ "name": "Idle" }, { "name": "Running", "fields": ["u32"] }]
+                    }
+                }
+            ]
+        })).unwrap()
+    }
+
+    pub(crate) fn test_counter_data(authority: &Pubkey) -> Vec<u8> {
+        let mut data = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        data.extend_from_slice(authority.as_ref());
+        data.extend_from_slice(&42u64.to_le_bytes());
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(b"abc");
+        data.extend_from_slice(&[1, 7, 0, 0, 0]);
+        data.push(0);
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&[255, 1]);
+        data.extend_from_slice(&u128::MAX.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_decode_account() {
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mut registry = IdlRegistry::default();
+        registry.insert(program_id, test_idl());
+
+        let decoded = registry.decode_account(program_id.as_ref(), &test_counter_data(&authority)).unwrap().unwrap();
+        assert_eq!(decoded, Decoded {
+            name: "Counter".to_string(),
+            value: json!({
+                "authority": authority.to_string(),
+                "count": 42,
+                "label": "abc",
+                "state": { "Running": [7] },
+                "limit": null,
+                "history": [-1, 1],
+                "total": u128::MAX.to_string(),
+            }),
+        });
+
+        assert!(registry.decode_account(Pubkey::new_unique().as_ref(), &[]).is_none());
+        assert!(registry.decode_account(program_id.as_ref(), &[1, 2, 3, 4, 5, 6, 7, 8, 1]).unwrap().is_err());
+        assert!(registry.decode_account(program_id.as_ref(), &[0; 64]).unwrap().is_err());
+    }
+
+    #[test]
+    fn test_decode_event() {
+        let program_id = Pubkey::new_unique();
+        let mut registry = IdlRegistry::default();
+        registry.insert(program_id, test_idl());
+
+        let mut data = vec![8, 7, 6, 5, 4, 3, 2, 1];
+        data.extend_from_slice(&43u64.to_le_bytes());
+        data.push(0);
+        let data = STANDARD.encode(data);
+
+        let decoded = registry.decode_event(&program_id.to_string(), &data).unwrap().unwrap();
+        assert_eq!(decoded, Decoded { name: "Incremented".to_string(), value: json!({ "count": 43, "state": "Idle" }) });
+
+        assert!(registry.decode_event(&Pubkey::new_unique().to_string(), &data).is_none());
+        assert!(registry.decode_event(&program_id.to_string(), "not base64!").unwrap().is_err());
+        assert!(registry.decode_event(&program_id.to_string(), &STANDARD.encode([0; 16])).unwrap().is_err());
+    }
+
+    #[test]
+    fn test_decode_legacy_event() {
+        let idl: Idl = serde_json::from_value(json!({
+            "version": "0.1.0",
+            "name": "counter",
+            "instructions": [],
+            "events": [{
+                "name": "Incremented",
+                "fields": [{ "name": "count", "type": "u64", "index": false }]
+            }]
+        })).unwrap();
+        idl.validate().unwrap();
+
+        let mut data = hash(b"event:Incremented").to_bytes()[..8].to_vec();
+        data.extend_from_slice(&7u64.to_le_bytes());
+
+        let decoded = idl.decode_event(&data).unwrap().unwrap();
+        assert_eq!(decoded, Decoded { name: "Incremented".to_string(), value: json!({ "count": 7 }) });
+    }
+
+    #[test]
+    fn test_decode_legacy_account() {
+        let idl: Idl = serde_json::from_value(json!({
+            "version": "0.1.0",
+            "name": "counter",
+            "instructions": [],
+            "accounts": [{
+                "name": "Counter",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        { "name": "authority", "type": "publicKey" },
+                        { "name": "bump", "type": { "array": ["u8", 2] } }
+                    ]
+                }
+            }]
+        })).unwrap();
+        idl.validate().unwrap();
+
+        let authority = Pubkey::new_unique();
+        let mut data = hash(b"account:Counter").to_bytes()[..8].to_vec();
+        data.extend_from_slice(authority.as_ref());
+        data.extend_from_slice(&[254, 255]);
+
+        let decoded = idl.decode_account(&data).unwrap().unwrap();
+        assert_eq!(decoded.name, "Counter");
+        assert_eq!(decoded.value, json!({ "authority": authority.to_string(), "bump": [254, 255] }));
+    }
+
+    #[test]
+    fn test_unsupported_types() {
+        let idl: Idl = serde_json::from_value(json!({
+            "accounts": [
+                { "name": "Counter", "discriminator": [1] },
+                { "name": "Wide", "discriminator": [2] },
+                { "name": "Pool", "discriminator": [3] }
+            ],
+            "events": [
+                { "name": "Wrapped", "discriminator": [4] },
+                { "name": "Legacy", "discriminator": [5], "fields": [{ "name": "state", "type": { "defined": "Option<State>" } }] }
+            ],
+            "types": [
+                { "name": "Counter", "type": { "kind": "struct", "fields": [{ "name": "count", "type": "u64" }] } },
+                { "name": "Wide", "type": { "kind": "struct", "fields": [{ "name": "supply", "type": { "option": "u256" } }] } },
+                { "name": "Pool", "type": { "kind": "struct", "fields": [{ "name": "value", "type": { "generic": "T" } }] } },
+                {
+                    "name": "Wrapped",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [{ "name": "inner", "type": { "defined": { "name": "Pool", "generics": [{ "kind": "type", "type": "u64" }] } } }]
+                    }
+                }
+            ]
+        })).unwrap();
+        idl.validate().unwrap();
+
+        let program_id = Pubkey::new_unique();
+        let mut registry = IdlRegistry::default();
+        registry.insert(program_id, idl);
+        assert_eq!(registry.programs[&program_id].unsupported(), vec!["account Wide", "account Pool", "event Wrapped", "event Legacy"]);
+
+        // The accounts and events using unsupported types are skipped, the others are still decoded
+        assert!(registry.decode_account(program_id.as_ref(), &[2, 0]).is_none());
+        assert!(registry.decode_account(program_id.as_ref(), &[3, 0]).is_none());
+        assert!(registry.decode_event(&program_id.to_string(), &STANDARD.encode([4, 0])).is_none());
+        assert!(registry.decode_event(&program_id.to_string(), &STANDARD.encode([5, 0])).is_none());
+        let decoded = registry.decode_account(program_id.as_ref(), &[1, 7, 0, 0, 0, 0, 0, 0, 0]).unwrap().unwrap();
+        assert_eq!(decoded, Decoded { name: "Counter".to_string(), value: json!({ "count": 7 }) });
+    }
+
+    #[test]
+    fn test_validate() {
+        let idl: Idl = serde_json::from_value(json!({
+            "accounts": [{ "name": "Missing", "discriminator": [1] }]
+        })).unwrap();
+        assert_eq!(idl.validate().unwrap_err(), "No type definition for account Missing");
+
+        let idl: Idl = serde_json::from_value(json!({
+            "events": [{ "name": "Missing", "discriminator": [1] }]
+        })).unwrap();
+        assert_eq!(idl.validate().unwrap_err(), "No type definition for event Missing");
+    }
+}
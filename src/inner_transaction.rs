@@ -1,35 +1,256 @@
 use agave_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfoVersions;
+use serde::Serialize;
 use solana_sdk::clock::UnixTimestamp;
 use solana_sdk::signature::Signature;
-use solana_sdk::transaction::SanitizedTransaction;
-use solana_transaction_status::UiTransactionStatusMeta;
+use solana_sdk::transaction::{SanitizedTransaction, TransactionError};
+use solana_transaction_status::{TransactionStatusMeta, TransactionTokenBalance, UiLoadedAddresses, UiTransactionTokenBalance};
+use sologger_log_context::programs_selector::ProgramsSelector;
 
+use crate::cpi_tree::CpiInstruction;
+use crate::sologger_config::TransactionFieldsConfig;
+
+/// The parts of a transaction notification needed to produce its structured logs. Only these are copied out of the
+/// validator's transaction, so the notification can be handed to the worker threads cheaply.
 #[derive(Debug, Clone)]
 pub struct ReplicaTransactionInfo {
     pub signature: Signature,
-    pub is_vote: bool,
-    pub transaction: SanitizedTransaction,
-    pub meta: UiTransactionStatusMeta,
+    pub log_messages: Vec<String>,
+    pub transaction_error: Option<TransactionError>,
     pub slot: u64,
     pub block_time: Option<UnixTimestamp>,
-    pub index: usize,
+    /// Set when the slot of the transaction was abandoned before reaching the configured commitment
+    pub orphaned: bool,
+    /// The transaction fields enabled by transactionFields, added to each structured log of the transaction
+    pub fields: Option<TransactionFields>,
+    /// The instructions of the transaction with their inner instructions, if cpiTree is enabled
+    pub cpi_tree: Option<Vec<CpiInstruction>>,
+}
+
+/// The transaction fields added to the structured logs. Only the fields enabled in transactionFields are copied out of the validator's transaction.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionFields {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fee: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compute_units_consumed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fee_payer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pre_balances: Option<Vec<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_balances: Option<Vec<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pre_token_balances: Option<Vec<UiTransactionTokenBalance>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_token_balances: Option<Vec<UiTransactionTokenBalance>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    loaded_addresses: Option<UiLoadedAddresses>,
+}
+
+impl TransactionFields {
+    /// Returns the fields of the transaction enabled in the config, or None if no field is enabled
+    pub fn new(transaction: &ReplicaTransactionInfoVersions, config: &TransactionFieldsConfig) -> Option<Self> {
+        if !config.is_enabled() {
+            return None;
+        }
+        let (sanitized_transaction, meta, index): (&SanitizedTransaction, &TransactionStatusMeta, Option<usize>) = match transaction {
+            ReplicaTransactionInfoVersions::V0_0_1(transaction) => (transaction.transaction, transaction.transaction_status_meta, None),
+            ReplicaTransactionInfoVersions::V0_0_2(transaction) => (transaction.transaction, transaction.transaction_status_meta, Some(transaction.index)),
+        };
+        let token_balances = |token_balances: &Vec<TransactionTokenBalance>| {
+            token_balances.iter().cloned().map(UiTransactionTokenBalance::from).collect()
+        };
+        Some(TransactionFields {
+            fee: config.fee.then_some(meta.fee),
+            compute_units_consumed: meta.compute_units_consumed.filter(|_| config.compute_units_consumed),
+            index: index.filter(|_| config.index),
+            fee_payer: config.fee_payer.then(|| sanitized_transaction.message().fee_payer().to_string()),
+            pre_balances: config.balances.then(|| meta.pre_balances.clone()),
+            post_balances: config.balances.then(|| meta.post_balances.clone()),
+            pre_token_balances: meta.pre_token_balances.as_ref().filter(|_| config.token_balances).map(token_balances),
+            post_token_balances: meta.post_token_balances.as_ref().filter(|_| config.token_balances).map(token_balances),
+            loaded_addresses: config.loaded_addresses.then(|| UiLoadedAddresses::from(&meta.loaded_addresses)),
+        })
+    }
 }
 
 impl<'a> From<(ReplicaTransactionInfoVersions<'a>, u64)> for ReplicaTransactionInfo {
     fn from((transaction, slot): (ReplicaTransactionInfoVersions<'a>, u64)) -> Self {
         match transaction {
-            ReplicaTransactionInfoVersions::V0_0_1(_transaction) => {
-                unreachable!("ReplicaTransactionInfoVersions::V0_0_1 is not supported")
-            }
+            ReplicaTransactionInfoVersions::V0_0_1(transaction) => Self {
+                signature: *transaction.signature,
+                log_messages: transaction.transaction_status_meta.log_messages.clone().unwrap_or_default(),
+                transaction_error: transaction.transaction_status_meta.status.clone().err(),
+                slot,
+                block_time: None,
+                orphaned: false,
+                fields: None,
+                cpi_tree: None,
+            },
             ReplicaTransactionInfoVersions::V0_0_2(transaction) => Self {
                 signature: *transaction.signature,
-                is_vote: transaction.is_vote,
-                transaction: transaction.transaction.clone(),
-                meta: transaction.transaction_status_meta.clone().into(),
+                log_messages: transaction.transaction_status_meta.log_messages.clone().unwrap_or_default(),
+                transaction_error: transaction.transaction_status_meta.status.clone().err(),
                 slot,
                 block_time: None,
-                index: transaction.index,
+                orphaned: false,
+                fields: None,
+                cpi_tree: None,
             },
         }
     }
-}
\ No newline at end of file
+}
+
+/// Returns true if the transaction is a simple vote transaction
+pub fn is_vote_transaction(transaction: &ReplicaTransactionInfoVersions) -> bool {
+    match transaction {
+        ReplicaTransactionInfoVersions::V0_0_1(transaction) => transaction.is_vote,
+        ReplicaTransactionInfoVersions::V0_0_2(transaction) => transaction.is_vote,
+    }
+}
+
+/// Returns true if the transaction produced logs and one of its account keys is a selected program.
+/// The account keys include every program the transaction invokes, directly or through CPI, so transactions
+/// that cannot produce any selected logs are skipped before they are copied.
+pub fn is_transaction_selected(transaction: &ReplicaTransactionInfoVersions, programs_selector: &ProgramsSelector) -> bool {
+    let (sanitized_transaction, log_messages) = match transaction {
+        ReplicaTransactionInfoVersions::V0_0_1(transaction) => {
+            (transaction.transaction, &transaction.transaction_status_meta.log_messages)
+        }
+        ReplicaTransactionInfoVersions::V0_0_2(transaction) => {
+            (transaction.transaction, &transaction.transaction_status_meta.log_messages)
+        }
+    };
+    match log_messages {
+        Some(log_messages) if !log_messages.is_empty() => {}
+        _ => return false,
+    }
+    programs_selector.select_all_programs || has_selected_account_key(sanitized_transaction, programs_selector)
+}
+
+fn has_selected_account_key(transaction: &SanitizedTransaction, programs_selector: &ProgramsSelector) -> bool {
+    transaction
+        .message()
+        .account_keys()
+        .iter()
+        .any(|account_key| programs_selector.is_program_selected(account_key.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use agave_geyser_plugin_interface::geyser_plugin_interface::{ReplicaTransactionInfo as ReplicaTransactionInfoV1, ReplicaTransactionInfoV2, ReplicaTransactionInfoVersions};
+    use solana_sdk::hash::Hash;
+    use solana_sdk::instruction::InstructionError;
+    use solana_sdk::signature::{Keypair, Signature, Signer};
+    use solana_sdk::system_transaction;
+    use solana_sdk::transaction::{SanitizedTransaction, TransactionError};
+    use solana_transaction_status::TransactionStatusMeta;
+    use sologger_log_context::programs_selector::ProgramsSelector;
+    use crate::inner_transaction::{is_transaction_selected, is_vote_transaction, ReplicaTransactionInfo, TransactionFields};
+    use crate::sologger_config::TransactionFieldsConfig;
+
+    fn test_transaction() -> SanitizedTransaction {
+        let keypair = Keypair::new();
+        let transaction = system_transaction::transfer(&keypair, &keypair.pubkey(), 42, Hash::default());
+        SanitizedTransaction::from_transaction_for_tests(transaction)
+    }
+
+    fn test_status_meta() -> TransactionStatusMeta {
+        TransactionStatusMeta {
+            status: Err(TransactionError::InstructionError(0, InstructionError::InsufficientFunds)),
+            log_messages: Some(vec![
+                "Program 11111111111111111111111111111111 invoke [1]".to_string(),
+                "Program 11111111111111111111111111111111 failed: insufficient funds for instruction".to_string(),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_from_v0_0_1() {
+        let signature = Signature::new_unique();
+        let transaction = test_transaction();
+        let transaction_status_meta = test_status_meta();
+        let transaction_info = ReplicaTransactionInfoV1 {
+            signature: &signature,
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &transaction_status_meta,
+        };
+        let transaction_info = ReplicaTransactionInfoVersions::V0_0_1(&transaction_info);
+
+        assert!(!is_vote_transaction(&transaction_info));
+        assert!(is_transaction_selected(&transaction_info, &ProgramsSelector::new(&["11111111111111111111111111111111".to_string()])));
+
+        let transaction_info = ReplicaTransactionInfo::from((transaction_info, 5));
+        assert_eq!(transaction_info.signature, signature);
+        assert_eq!(transaction_info.log_messages.len(), 2);
+        assert!(transaction_info.transaction_error.is_some());
+        assert_eq!(transaction_info.slot, 5);
+    }
+
+    #[test]
+    fn test_from_v0_0_2() {
+        let signature = Signature::new_unique();
+        let transaction = test_transaction();
+        let transaction_status_meta = test_status_meta();
+        let transaction_info = ReplicaTransactionInfoV2 {
+            signature: &signature,
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &transaction_status_meta,
+            index: 3,
+        };
+        let transaction_info = ReplicaTransactionInfoVersions::V0_0_2(&transaction_info);
+
+        assert!(!is_vote_transaction(&transaction_info));
+        assert!(!is_transaction_selected(&transaction_info, &ProgramsSelector::new(&["TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string()])));
+
+        let transaction_info = ReplicaTransactionInfo::from((transaction_info, 5));
+        assert_eq!(transaction_info.signature, signature);
+        assert_eq!(transaction_info.log_messages.len(), 2);
+        assert!(transaction_info.transaction_error.is_some());
+        assert_eq!(transaction_info.slot, 5);
+    }
+
+    #[test]
+    fn test_transaction_fields() {
+        let signature = Signature::new_unique();
+        let transaction = test_transaction();
+        let transaction_status_meta = TransactionStatusMeta {
+            fee: 5000,
+            pre_balances: vec![100, 0],
+            post_balances: vec![95, 5],
+            compute_units_consumed: Some(150),
+            ..test_status_meta()
+        };
+        let transaction_info = ReplicaTransactionInfoV2 {
+            signature: &signature,
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &transaction_status_meta,
+            index: 3,
+        };
+        let transaction_info = ReplicaTransactionInfoVersions::V0_0_2(&transaction_info);
+
+        assert_eq!(TransactionFields::new(&transaction_info, &TransactionFieldsConfig::default()), None);
+
+        let config = TransactionFieldsConfig { fee: true, compute_units_consumed: true, index: true, fee_payer: true, balances: true, ..Default::default() };
+        let json = serde_json::to_value(TransactionFields::new(&transaction_info, &config).unwrap()).unwrap();
+        assert_eq!(json, serde_json::json!({
+            "fee": 5000,
+            "computeUnitsConsumed": 150,
+            "index": 3,
+            "feePayer": transaction.message().fee_payer().to_string(),
+            "preBalances": [100, 0],
+            "postBalances": [95, 5],
+        }));
+
+        let config = TransactionFieldsConfig { token_balances: true, loaded_addresses: true, ..Default::default() };
+        let json = serde_json::to_value(TransactionFields::new(&transaction_info, &config).unwrap()).unwrap();
+        assert_eq!(json, serde_json::json!({ "loadedAddresses": { "writable": [], "readonly": [] } }));
+    }
+}
@@ -6,4 +6,18 @@ mod logger_lib;
 mod sologger_config;
 mod inner_transaction;
 mod log_processor;
+mod task_queue;
 mod config_loader;
+mod config_watcher;
+mod block_time_cache;
+mod commitment_buffer;
+mod slot_events;
+mod block_events;
+mod accounts_selector;
+mod account_events;
+mod idl;
+mod cpi_tree;
+#[cfg(feature = "enable_otel")]
+mod otel;
+#[cfg(feature = "enable_otel")]
+mod transaction_trace;
@@ -1,43 +1,264 @@
+use std::collections::HashMap;
 use anyhow::Result;
-use log::{error, info};
-use agave_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfoV2;
-use solana_transaction_status::option_serializer::OptionSerializer;
+use log::{debug, error, info};
+use serde_json::{json, Value};
 use sologger_log_context::programs_selector::ProgramsSelector;
 use sologger_log_context::sologger_log_context::LogContext;
 
+use crate::idl::IdlRegistry;
 use crate::inner_transaction::ReplicaTransactionInfo;
+#[cfg(feature = "enable_otel")]
+use crate::otel::ProgramMetrics;
+#[cfg(feature = "enable_otel")]
+use opentelemetry::KeyValue;
 
 pub fn from_rpc_response(
     transaction: &ReplicaTransactionInfo,
-    program_selector: ProgramsSelector,
+    program_selector: &ProgramsSelector,
     slot: u64
 ) -> Result<Vec<LogContext>> {
-    let log_contexts = match &transaction.meta.log_messages {
-        OptionSerializer::Some(logs) => {
-            let transaction_error = transaction.meta.status.clone().err()
-                .map_or_else(|| "".to_string(), |err| err.to_string());
-            let sig = transaction.signature.to_string();
-            LogContext::parse_logs(
-                &logs,
-                transaction_error,
-                &program_selector,
-                slot,
-                sig,
-            )
-        }
-        OptionSerializer::None | OptionSerializer::Skip => Vec::new(),
-    };
+    let transaction_error = transaction.transaction_error.as_ref()
+        .map_or_else(|| "".to_string(), |err| err.to_string());
+    let sig = transaction.signature.to_string();
+    let log_contexts = LogContext::parse_logs(
+        &transaction.log_messages,
+        transaction_error,
+        program_selector,
+        slot,
+        sig,
+    );
     Ok(log_contexts)
 }
 
 
-pub fn log_contexts_from_logs(log_contexts: &Vec<LogContext>) -> Result<()> {
+pub(crate) fn log_contexts_from_logs(log_contexts: &Vec<LogContext>, transaction: &ReplicaTransactionInfo, program_names: &HashMap<String, String>, idls: &IdlRegistry) -> Result<()> {
     for log_context in log_contexts {
+        // The program ID is passed as a key-value as well, so the sinks can filter the logs by program without parsing them
+        let program_id = log_context.program_id.as_str();
         if log_context.has_errors() {
-            error!(target: "sologger_geyser_plugin::log_processor::error", "{}", &log_context.to_json());
+            error!(target: "sologger_geyser_plugin::log_processor::error", program_id; "{}", to_json(log_context, transaction, program_names, idls));
         } else {
-            info!(target: "sologger_geyser_plugin::log_processor::info", "{}", &log_context.to_json());
+            info!(target: "sologger_geyser_plugin::log_processor::info", program_id; "{}", to_json(log_context, transaction, program_names, idls));
         }
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Records the invocation, failure and compute units metrics of each program invocation, and the number of logs of the transaction
+#[cfg(feature = "enable_otel")]
+pub(crate) fn record_metrics(log_contexts: &[LogContext], transaction: &ReplicaTransactionInfo, program_names: &HashMap<String, String>, metrics: &ProgramMetrics) {
+    for log_context in log_contexts {
+        let mut attributes = vec![KeyValue::new("solana.program_id", log_context.program_id.clone())];
+        if let Some(program_name) = program_names.get(&log_context.program_id) {
+            attributes.push(KeyValue::new("solana.program_name", program_name.clone()));
+        }
+        metrics.invocations.add(1, &attributes);
+        if log_context.has_errors() {
+            metrics.failures.add(1, &attributes);
+        }
+        if let Some(compute_units) = compute_units_consumed(log_context) {
+            metrics.compute_units.record(compute_units, &attributes);
+        }
+    }
+    metrics.transaction_logs.record(transaction.log_messages.len() as u64, &[]);
+}
+
+/// Returns the compute units consumed by the program invocation, taken from its `Program <id> consumed <n> of <m> compute units` log
+pub fn compute_units_consumed(log_context: &LogContext) -> Option<u64> {
+    let prefix = format!("Program {} consumed ", log_context.program_id);
+    log_context.raw_logs
+        .iter()
+        .find_map(|log| log.strip_prefix(&prefix)?.split(' ').next()?.parse().ok())
+}
+
+/// Decodes the `Program data:` logs of the invocation with the IDL of its program, skipping the logs that are not events of the IDL
+fn decode_events(log_context: &LogContext, idls: &IdlRegistry) -> Vec<Value> {
+    log_context.data_logs
+        .iter()
+        .filter_map(|data| match idls.decode_event(&log_context.program_id, data)? {
+            Ok(decoded) => Some(json!({ "name": decoded.name, "data": decoded.value })),
+            Err(err) => {
+                debug!("Unable to decode the program data of {}: {}", log_context.id, err);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Converts the LogContext to a JSON string, adding a `programName` field if the program ID has a configured alias,
+/// a `blockTime` field if the block time of the transaction's slot is known, `"orphaned": true` if the slot was abandoned,
+/// an `events` field with the Anchor events decoded from the `Program data:` logs if the program has an IDL,
+/// and a `transaction` field with the transaction fields enabled by transactionFields
+fn to_json(log_context: &LogContext, transaction: &ReplicaTransactionInfo, program_names: &HashMap<String, String>, idls: &IdlRegistry) -> String {
+    let program_name = program_names.get(&log_context.program_id);
+    let events = decode_events(log_context, idls);
+    if program_name.is_none() && transaction.block_time.is_none() && !transaction.orphaned && events.is_empty() && transaction.fields.is_none() {
+        return log_context.to_json();
+    }
+    let mut value = serde_json::to_value(log_context).unwrap_or_default();
+    if let Some(program_name) = program_name {
+        value["programName"] = serde_json::Value::from(program_name.as_str());
+    }
+    if let Some(block_time) = transaction.block_time {
+        value["blockTime"] = serde_json::Value::from(block_time);
+    }
+    if transaction.orphaned {
+        value["orphaned"] = serde_json::Value::from(true);
+    }
+    if !events.is_empty() {
+        value["events"] = Value::Array(events);
+    }
+    if let Some(fields) = &transaction.fields {
+        value["transaction"] = serde_json::to_value(fields).unwrap_or_default();
+    }
+    value.to_string()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::HashMap;
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use solana_sdk::pubkey::Pubkey;
+    use sologger_log_context::sologger_log_context::LogContext;
+    use crate::idl::IdlRegistry;
+    use crate::idl::tests::test_idl;
+    use crate::inner_transaction::{ReplicaTransactionInfo, TransactionFields};
+    use crate::log_processor::{compute_units_consumed, to_json};
+
+    /// An empty top level invocation of the program, at `instruction_index` of a transaction in `slot`
+    pub(crate) fn log_context(program_id: &str, instruction_index: usize, slot: usize) -> LogContext {
+        LogContext::new(program_id.to_string(), 1, format!("{}-1-{}", program_id, instruction_index), instruction_index, slot, "".to_string())
+    }
+
+    fn test_transaction(block_time: Option<i64>) -> ReplicaTransactionInfo {
+        ReplicaTransactionInfo {
+            signature: Default::default(),
+            log_messages: vec![],
+            transaction_error: None,
+            slot: 1,
+            block_time,
+            orphaned: false,
+            fields: None,
+            cpi_tree: None,
+        }
+    }
+
+    #[test]
+    pub fn test_to_json_program_name() {
+        let log_context = log_context("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", 0, 1);
+        let mut program_names = HashMap::new();
+        let transaction = test_transaction(None);
+
+        let json: serde_json::Value = serde_json::from_str(&to_json(&log_context, &transaction, &program_names, &IdlRegistry::default())).unwrap();
+        assert!(json.get("programName").is_none());
+
+        program_names.insert("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string(), "token".to_string());
+        let json: serde_json::Value = serde_json::from_str(&to_json(&log_context, &transaction, &program_names, &IdlRegistry::default())).unwrap();
+        assert_eq!(json["programName"], "token");
+        assert_eq!(json["program_id"], "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+    }
+
+    #[test]
+    pub fn test_to_json_block_time() {
+        let log_context = log_context("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", 0, 1);
+        let program_names = HashMap::new();
+
+        let json: serde_json::Value = serde_json::from_str(&to_json(&log_context, &test_transaction(None), &program_names, &IdlRegistry::default())).unwrap();
+        assert!(json.get("blockTime").is_none());
+
+        let json: serde_json::Value = serde_json::from_str(&to_json(&log_context, &test_transaction(Some(1700000000)), &program_names, &IdlRegistry::default())).unwrap();
+        assert_eq!(json["blockTime"], 1700000000);
+    }
+
+    #[test]
+    pub fn test_to_json_transaction_fields() {
+        let log_context = log_context("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", 0, 1);
+        let program_names = HashMap::new();
+
+        let json: serde_json::Value = serde_json::from_str(&to_json(&log_context, &test_transaction(None), &program_names, &IdlRegistry::default())).unwrap();
+        assert!(json.get("transaction").is_none());
+
+        let transaction = ReplicaTransactionInfo { fields: Some(TransactionFields::default()), ..test_transaction(None) };
+        let json: serde_json::Value = serde_json::from_str(&to_json(&log_context, &transaction, &program_names, &IdlRegistry::default())).unwrap();
+        assert_eq!(json["transaction"], serde_json::json!({}));
+    }
+
+    #[test]
+    pub fn test_compute_units_consumed() {
+        let mut log_context = log_context("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", 0, 1);
+        assert_eq!(compute_units_consumed(&log_context), None);
+
+        log_context.raw_logs.push("Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA invoke [1]".to_string());
+        log_context.raw_logs.push("Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA consumed 4645 of 200000 compute units".to_string());
+        assert_eq!(compute_units_consumed(&log_context), Some(4645));
+    }
+
+    #[test]
+    pub fn test_to_json_events() {
+        let program_id = Pubkey::new_unique();
+        let mut idls = IdlRegistry::default();
+        idls.insert(program_id, test_idl());
+        let mut log_context = log_context(&program_id.to_string(), 0, 1);
+        let program_names = HashMap::new();
+
+        let json: serde_json::Value = serde_json::from_str(&to_json(&log_context, &test_transaction(None), &program_names, &idls)).unwrap();
+        assert!(json.get("events").is_none());
+
+        let mut data = vec![8, 7, 6, 5, 4, 3, 2, 1];
+        data.extend_from_slice(&43u64.to_le_bytes());
+        data.push(0);
+        log_context.data_logs.push(STANDARD.encode(data));
+        log_context.data_logs.push(STANDARD.encode([0; 16]));
+        let json: serde_json::Value = serde_json::from_str(&to_json(&log_context, &test_transaction(None), &program_names, &idls)).unwrap();
+        assert_eq!(json["events"], serde_json::json!([{ "name": "Incremented", "data": { "count": 43, "state": "Idle" } }]));
+    }
+
+    #[cfg(feature = "enable_otel")]
+    #[test]
+    pub fn test_record_metrics() {
+        use std::time::Duration;
+        use opentelemetry_proto::tonic::metrics::v1::{metric::Data, number_data_point};
+        use sologger_log_transport::opentelemetry_config::OpentelemetryConfig;
+        use crate::log_processor::record_metrics;
+        use crate::otel::{program_metrics, tests::{start_collector, GLOBAL_PROVIDERS}, OtelRuntime};
+
+        let _global_providers = GLOBAL_PROVIDERS.lock().unwrap_or_else(|err| err.into_inner());
+        let mut runtime = OtelRuntime::start().unwrap();
+        let (endpoint, collector) = start_collector(runtime.handle());
+        let otel_config = OpentelemetryConfig { metrics_endpoint: endpoint, ..Default::default() };
+        runtime.start_metrics(&otel_config, Duration::from_secs(60)).unwrap();
+        let metrics = program_metrics().unwrap();
+
+        let mut succeeded = log_context("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", 0, 2);
+        succeeded.raw_logs.push("Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA consumed 1200 of 200000 compute units".to_string());
+        let mut failed = log_context("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", 1, 2);
+        failed.errors.push("custom program error: 0x1".to_string());
+        let transaction = ReplicaTransactionInfo { log_messages: vec!["Program log: transfer".to_string(); 12], ..test_transaction(None) };
+        let program_names = HashMap::from([("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string(), "token".to_string())]);
+        record_metrics(&[succeeded, failed], &transaction, &program_names, &metrics);
+        // Shutting down the runtime exports the metrics
+        drop(runtime);
+
+        let metrics: HashMap<String, Data> = collector.metrics
+            .try_iter()
+            .flat_map(|request| request.resource_metrics)
+            .flat_map(|resource_metrics| resource_metrics.scope_metrics)
+            .flat_map(|scope_metrics| scope_metrics.metrics)
+            .filter_map(|metric| Some((metric.name, metric.data?)))
+            .collect();
+        let sum = |name: &str| match &metrics[name] {
+            Data::Sum(sum) => sum.data_points[0].value,
+            data => panic!("{} is not a sum: {:?}", name, data),
+        };
+        assert_eq!(sum("solana.program.invocations"), Some(number_data_point::Value::AsInt(2)));
+        assert_eq!(sum("solana.program.failures"), Some(number_data_point::Value::AsInt(1)));
+        let Data::Histogram(compute_units) = &metrics["solana.program.compute_units"] else { panic!("compute units is not a histogram") };
+        assert_eq!(compute_units.data_points[0].count, 1);
+        assert_eq!(compute_units.data_points[0].sum, Some(1200.0));
+        assert_eq!(compute_units.data_points[0].attributes.len(), 2);
+        let Data::Histogram(transaction_logs) = &metrics["solana.transaction.logs"] else { panic!("logs is not a histogram") };
+        assert_eq!(transaction_logs.data_points[0].sum, Some(12.0));
+        assert_eq!(transaction_logs.data_points[0].explicit_bounds[0], 5.0);
+    }
+}
@@ -1,69 +1,547 @@
 #![cfg_attr(debug_assertions, allow(dead_code, unused_imports))]
-use std::path::Path;
-use log4rs::init_file;
-use log::{debug, error};
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::{LineWriter, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
+use log4rs::config::{load_config_file, Deserializers};
+use log::kv::{self, Key, VisitSource};
+use log::{debug, warn, Level, LevelFilter, Log, Metadata, Record};
+use serde_json::{json, Map, Value};
 
-use crate::sologger_config::SologgerConfig;
+#[cfg(feature = "enable_otel")]
+use crate::otel::OtelRuntime;
+use crate::sologger_config::{SinkConfig, SologgerConfig, TransportConfig};
+
+/// The default maximum number of logs waiting to be sent by a sink
+pub const DEFAULT_MAX_QUEUED_LOGS: usize = 10_000;
+
+/// The minimum time between two log lines reporting the logs dropped by a sink
+const DROPPED_LOGS_REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long flushing the logger waits for each sink to send its queued logs
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
 
-pub fn init_logger(sologger_config: &SologgerConfig) {
-    #[cfg(feature = "enable_logstash")]
-    init_logger_logstash(sologger_config);
+/// The global logger. The `log` facade only accepts a single global logger per process, so it is installed once and the sinks of each load of the plugin are swapped into it.
+static LOGGER: SinkLogger = SinkLogger::new();
+
+/// Set once installing LOGGER as the global logger was attempted, to whether it succeeded
+static LOGGER_INSTALLED: OnceLock<bool> = OnceLock::new();
+
+/// Initializes the sinks of the transport config and installs them in the global logger, replacing the sinks of a previous load, and starts the traces and metrics exporters if they are enabled.
+/// The returned runtime must be kept until the plugin is unloaded, see [TransportRuntime::shutdown].
+pub fn init_logger(sologger_config: &SologgerConfig) -> Result<TransportRuntime, TransportError> {
+    let mut transport_runtime = TransportRuntime::default();
+    // On error, the sinks and runtimes started so far are shut down when they are dropped
+    let sinks = create_sinks(sologger_config, &mut transport_runtime)?;
     #[cfg(feature = "enable_otel")]
-    init_log4rs(&sologger_config.log4rs_config_location).expect("Error initializing log4rs for enable_otel feature");
+    if sologger_config.traces {
+        let error = |msg: String| TransportError { transport: "traces", msg };
+        let otel_config = crate::otel::read_otel_config(&sologger_config.opentelemetry_config_location).map_err(error)?;
+        transport_runtime.otel()?.start_traces(&otel_config).map_err(error)?;
+    }
     #[cfg(feature = "enable_otel")]
-    init_logger_otel(sologger_config);
+    if sologger_config.metrics {
+        let error = |msg: String| TransportError { transport: "metrics", msg };
+        let otel_config = crate::otel::read_otel_config(&sologger_config.opentelemetry_config_location).map_err(error)?;
+        let export_interval = std::time::Duration::from_secs(sologger_config.metrics_export_interval_secs);
+        transport_runtime.otel()?.start_metrics(&otel_config, export_interval).map_err(error)?;
+    }
+    if sinks.is_empty() && LOGGER_INSTALLED.get().is_none() {
+        return Ok(transport_runtime);
+    }
+
+    let names: Vec<&str> = sinks.iter().map(|sink| sink.name).collect();
+    if !*LOGGER_INSTALLED.get_or_init(|| log::set_logger(&LOGGER).is_ok()) {
+        warn!("Another logger is already installed, the {} transports are not used", names.join(", "));
+        return Ok(transport_runtime);
+    }
+    let sinks = Arc::new(sinks);
+    LOGGER.set_sinks(Some(Arc::clone(&sinks)));
+    transport_runtime.sinks = Some(sinks);
+    debug!("Logger initialized with the {} transports", names.join(", "));
+    Ok(transport_runtime)
 }
 
-#[cfg(feature = "enable_logstash")]
-pub fn init_logger_logstash(sologger_config: &SologgerConfig) {
-    if !Path::new(&sologger_config.log4rs_config_location).exists() {
-        panic!("Log4rs config file not found");
-    };
-    sologger_log_transport::logstash_lib::init_logstash_logger(
-        &sologger_config.log4rs_config_location,
-    )
-    .expect("Logger not initialized");
+/// Applies the level and program filters of the reloaded transport config to the installed sinks.
+/// The transports themselves are only created when the plugin is loaded.
+pub fn apply_sink_filters(transport: &TransportConfig) {
+    if LOGGER.sinks().is_some() {
+        LOGGER.apply_filters(transport);
+        log::set_max_level(LOGGER.max_level());
+    }
+}
+
+/// The sinks and runtimes of the transports, which live as long as the plugin
+#[derive(Default)]
+pub struct TransportRuntime {
+    /// The sinks installed in the global logger
+    sinks: Option<Arc<Vec<Sink>>>,
+    #[cfg(feature = "enable_otel")]
+    otel: Option<OtelRuntime>,
+}
+
+impl TransportRuntime {
+    /// Returns the OpenTelemetry runtime, starting it on first use
+    #[cfg(feature = "enable_otel")]
+    fn otel(&mut self) -> Result<&mut OtelRuntime, TransportError> {
+        if self.otel.is_none() {
+            self.otel = Some(OtelRuntime::start().map_err(|msg| TransportError { transport: "otel", msg })?);
+        }
+        Ok(self.otel.as_mut().unwrap())
+    }
+
+    /// Removes the sinks from the global logger and stops their threads once their queued logs are sent,
+    /// then flushes and shuts down the OpenTelemetry providers and stops their runtime
+    pub fn shutdown(&mut self) {
+        if let Some(sinks) = self.sinks.take() {
+            LOGGER.remove_sinks(&sinks);
+            for sink in sinks.iter() {
+                sink.stop(FLUSH_TIMEOUT);
+            }
+        }
+        #[cfg(feature = "enable_otel")]
+        if let Some(otel) = self.otel.take() {
+            debug!("Shutting down the OpenTelemetry runtime");
+            drop(otel);
+        }
+    }
 }
 
+impl Drop for TransportRuntime {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Creates a sink for each transport of the config, and starts the runtimes they need
+fn create_sinks(sologger_config: &SologgerConfig, transport_runtime: &mut TransportRuntime) -> Result<Vec<Sink>, TransportError> {
+    // Only the otel transport starts a runtime
+    #[cfg(not(feature = "enable_otel"))]
+    let _ = transport_runtime;
+    let transport = sologger_config.transport();
+    let mut sinks = Vec::new();
+    for (name, sink_config) in transport.sinks() {
+        let (logger, level): (Box<dyn Log>, LevelFilter) = match name {
+            "log4rs" => log4rs_logger(name, &sologger_config.log4rs_config_location, Deserializers::default())?,
+            #[cfg(feature = "enable_logstash")]
+            "logstash" => {
+                use qoollo_log4rs_logstash::config::DeserializersExt;
+                log4rs_logger(name, &sologger_config.log4rs_config_location, Deserializers::default().with_logstash())?
+            }
+            #[cfg(feature = "enable_otel")]
+            "otel" => otel_logger(&sologger_config.opentelemetry_config_location, transport_runtime.otel()?)?,
+            "stdout" => (Box::new(JsonLinesLogger::new(std::io::stdout())), LevelFilter::Info),
+            "file" => {
+                let Some(path) = transport.file.as_ref().map(|file| &file.path) else { continue };
+                let writer = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|err| TransportError { transport: name, msg: format!("{}: {}", path, err) })?;
+                (Box::new(JsonLinesLogger::new(LineWriter::new(writer))), LevelFilter::Info)
+            }
+            // The config loader rejects the transports that were not compiled in
+            _ => continue,
+        };
+        sinks.push(Sink::new(name, Arc::from(logger), level, &sink_config)?);
+    }
+    Ok(sinks)
+}
+
+/// Returns the log4rs logger and the most verbose level of its config
+fn log4rs_logger(name: &'static str, log4rs_config_location: &str, deserializers: Deserializers) -> Result<(Box<dyn Log>, LevelFilter), TransportError> {
+    let config = load_config_file(log4rs_config_location, deserializers)
+        .map_err(|err| TransportError { transport: name, msg: format!("{}: {}", log4rs_config_location, err) })?;
+    let logger = log4rs::Logger::new(config);
+    let level = logger.max_log_level();
+    Ok((Box::new(logger), level))
+}
+
+/// Returns the OpenTelemetry logger and the level of its config. The exporter sends its batches on the runtime, which shuts down the provider when the plugin is unloaded.
 #[cfg(feature = "enable_otel")]
-pub fn init_logger_otel(sologger_config: &SologgerConfig) {
+fn otel_logger(opentelemetry_config_location: &str, runtime: &mut OtelRuntime) -> Result<(Box<dyn Log>, LevelFilter), TransportError> {
+    use opentelemetry_appender_log::OpenTelemetryLogBridge;
+    use opentelemetry_otlp::WithExportConfig;
+    use crate::otel::{read_otel_config, resource};
 
-    let rt =  tokio::runtime::Runtime::new().unwrap();
-    if !Path::new(&sologger_config.opentelemetry_config_location).exists() {
-        panic!("OTel config file not found {}", &sologger_config.opentelemetry_config_location);
+    let error = |msg: String| TransportError { transport: "otel", msg };
+    let config = read_otel_config(opentelemetry_config_location).map_err(error)?;
+    let provider = {
+        let _guard = runtime.handle().enter();
+        opentelemetry_otlp::new_pipeline()
+            .logging()
+            .with_resource(resource(&config))
+            .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(&config.endpoint))
+            .install_batch(opentelemetry_sdk::runtime::Tokio)
+            .map_err(|err| error(err.to_string()))?
     };
+    runtime.set_logger_provider(provider.clone());
+    let level = log::Level::from_str(&config.log_level).unwrap_or(log::Level::Error).to_level_filter();
+    let bridge = OpenTelemetryLogBridge::new(&provider);
+    Ok((Box::new(OtelLogger { bridge, provider }), level))
+}
+
+/// Sends the logs to the OpenTelemetry collector through the log bridge
+#[cfg(feature = "enable_otel")]
+struct OtelLogger {
+    bridge: opentelemetry_appender_log::OpenTelemetryLogBridge<opentelemetry_sdk::logs::LoggerProvider, opentelemetry_sdk::logs::Logger>,
+    provider: opentelemetry_sdk::logs::LoggerProvider,
+}
+
+#[cfg(feature = "enable_otel")]
+impl Log for OtelLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.bridge.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        self.bridge.log(record);
+    }
+
+    fn flush(&self) {
+        self.provider.force_flush();
+    }
+}
+
+/// A record whose message is formatted, so it can be sent to the sink threads
+struct OwnedRecord {
+    level: Level,
+    target: String,
+    message: String,
+    module_path: Option<String>,
+    file: Option<String>,
+    line: Option<u32>,
+    key_values: Vec<(String, String)>,
+}
+
+impl OwnedRecord {
+    fn new(record: &Record) -> Self {
+        let mut key_values = KeyValues(Vec::new());
+        let _ = record.key_values().visit(&mut key_values);
+        OwnedRecord {
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+            module_path: record.module_path().map(str::to_string),
+            file: record.file().map(str::to_string),
+            line: record.line(),
+            key_values: key_values.0,
+        }
+    }
+
+    fn log(&self, logger: &dyn Log) {
+        logger.log(&Record::builder()
+            .level(self.level)
+            .target(&self.target)
+            .module_path(self.module_path.as_deref())
+            .file(self.file.as_deref())
+            .line(self.line)
+            .key_values(&self.key_values)
+            .args(format_args!("{}", self.message))
+            .build());
+    }
+}
+
+/// Collects the key-values of a record, such as its program_id, so the sinks receive them with the message
+struct KeyValues(Vec<(String, String)>);
+
+impl<'kvs> VisitSource<'kvs> for KeyValues {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        self.0.push((key.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
+enum SinkMessage {
+    Record(Arc<OwnedRecord>),
+    /// Flushes the logger once the records queued before it are sent, then acknowledges
+    Flush(Sender<()>),
+    /// Flushes the logger once the records queued before it are sent, acknowledges and stops the thread of the sink
+    Stop(Sender<()>),
+}
+
+/// A log output with its filters. Records are queued and sent by a dedicated thread, so a slow sink only drops its own records rather than holding back the other sinks.
+struct Sink {
+    name: &'static str,
+    logger: Arc<dyn Log>,
+    /// The level of the logger's own config, used when the sink config has no level
+    default_level: LevelFilter,
+    filter: RwLock<SinkFilter>,
+    sender: Sender<SinkMessage>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+    dropped: AtomicU64,
+    last_report: Mutex<Option<Instant>>,
+}
+
+impl Sink {
+    /// Starts the thread sending the records of the sink. It stops once the sink is stopped or dropped.
+    fn new(name: &'static str, logger: Arc<dyn Log>, default_level: LevelFilter, config: &SinkConfig) -> Result<Self, TransportError> {
+        let (sender, receiver) = bounded(config.max_queued_logs.unwrap_or(DEFAULT_MAX_QUEUED_LOGS));
+        let thread_logger = Arc::clone(&logger);
+        let thread = std::thread::Builder::new()
+            .name(format!("sologger-{}", name))
+            .spawn(move || send_records(receiver, thread_logger.as_ref()))
+            .map_err(|err| TransportError { transport: name, msg: err.to_string() })?;
+        Ok(Sink {
+            name,
+            logger,
+            default_level,
+            filter: RwLock::new(SinkFilter::new(config, default_level)),
+            sender,
+            thread: Mutex::new(Some(thread)),
+            dropped: AtomicU64::new(0),
+            last_report: Mutex::new(None),
+        })
+    }
+
+    /// Returns true if the sink takes records of this level and target, and of this program if they belong to one
+    fn accepts(&self, metadata: &Metadata, program_id: Option<&str>) -> bool {
+        let filter = self.filter.read().unwrap();
+        let program_selected = match program_id {
+            Some(program_id) => filter.programs.is_empty() || filter.programs.contains(program_id),
+            None => true,
+        };
+        metadata.level() <= filter.level && program_selected && self.logger.enabled(metadata)
+    }
+
+    fn level(&self) -> LevelFilter {
+        self.filter.read().unwrap().level
+    }
+
+    /// Replaces the level and program filters with those of the reloaded sink config
+    fn set_filter(&self, config: &SinkConfig) {
+        *self.filter.write().unwrap() = SinkFilter::new(config, self.default_level);
+    }
+
+    /// Queues the record, dropping it if the queue of the sink is full
+    fn send(&self, record: Arc<OwnedRecord>) {
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(SinkMessage::Record(record)) {
+            self.record_drop();
+        }
+    }
+
+    fn record_drop(&self) {
+        let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+        let report_due = {
+            let mut last_report = self.last_report.lock().unwrap();
+            let report_due = match *last_report {
+                Some(last_report) => last_report.elapsed() >= DROPPED_LOGS_REPORT_INTERVAL,
+                None => true,
+            };
+            if report_due {
+                *last_report = Some(Instant::now());
+            }
+            report_due
+        };
+        // The lock is released first, as the report is logged through this sink as well
+        if report_due {
+            warn!(target: "sologger_geyser_plugin::logger_lib", "{}", json!({
+                "event": "logsDropped",
+                "transport": self.name,
+                "dropped": dropped,
+                "capacity": self.sender.capacity().unwrap_or_default(),
+            }));
+        }
+    }
 
-    let config = sologger_config.clone();
-    rt.spawn(async move {
-        println!("Starting OTel in Tokio RT");
+    /// Waits at most `timeout` for the queued records to be sent, then flushes the logger
+    fn flush(&self, timeout: Duration) {
+        let (ack_sender, ack_receiver) = bounded(1);
+        if self.sender.send_timeout(SinkMessage::Flush(ack_sender), timeout).is_ok() {
+            let _ = ack_receiver.recv_timeout(timeout);
+        }
+    }
+
+    /// Waits at most `timeout` for the queued records to be sent, then stops and joins the thread of the sink.
+    /// The thread is left to stop once the sink is dropped if it does not stop in time.
+    fn stop(&self, timeout: Duration) {
+        let Some(thread) = self.thread.lock().unwrap().take() else { return };
+        let (ack_sender, ack_receiver) = bounded(1);
+        if self.sender.send_timeout(SinkMessage::Stop(ack_sender), timeout).is_ok() && ack_receiver.recv_timeout(timeout).is_ok() {
+            let _ = thread.join();
+        } else {
+            warn!("The {} transport did not send its queued logs within {:?}", self.name, timeout);
+        }
+    }
+}
+
+/// The level and programs of the logs a sink accepts, replaced when the config is reloaded
+struct SinkFilter {
+    level: LevelFilter,
+    /// The programs whose logs are sent, all of them if empty
+    programs: HashSet<String>,
+}
+
+impl SinkFilter {
+    fn new(config: &SinkConfig, default_level: LevelFilter) -> Self {
+        SinkFilter {
+            level: config.level.as_deref().and_then(|level| LevelFilter::from_str(level).ok()).unwrap_or(default_level),
+            programs: config.programs.iter().filter(|program| *program != "*").cloned().collect(),
+        }
+    }
+}
+
+fn send_records(receiver: Receiver<SinkMessage>, logger: &dyn Log) {
+    for message in receiver {
+        match message {
+            SinkMessage::Record(record) => record.log(logger),
+            SinkMessage::Flush(ack) => {
+                logger.flush();
+                let _ = ack.send(());
+            }
+            SinkMessage::Stop(ack) => {
+                logger.flush();
+                let _ = ack.send(());
+                return;
+            }
+        }
+    }
+}
+
+/// The global logger, which queues each record on every sink that accepts it
+struct SinkLogger {
+    sinks: RwLock<Option<Arc<Vec<Sink>>>>,
+}
+
+impl SinkLogger {
+    const fn new() -> Self {
+        SinkLogger { sinks: RwLock::new(None) }
+    }
+
+    fn sinks(&self) -> Option<Arc<Vec<Sink>>> {
+        self.sinks.read().unwrap().clone()
+    }
+
+    /// Replaces the sinks, and sets the maximum level of the `log` facade to the most verbose level of the new sinks
+    fn set_sinks(&self, sinks: Option<Arc<Vec<Sink>>>) {
+        *self.sinks.write().unwrap() = sinks;
+        log::set_max_level(self.max_level());
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.sinks().iter().flat_map(|sinks| sinks.iter()).map(Sink::level).max().unwrap_or(LevelFilter::Off)
+    }
+
+    /// Applies the level and program filters of the transport config to the sinks of the same transports
+    fn apply_filters(&self, transport: &TransportConfig) {
+        let sink_configs = transport.sinks();
+        for sink in self.sinks().iter().flat_map(|sinks| sinks.iter()) {
+            if let Some((_, sink_config)) = sink_configs.iter().find(|(name, _)| *name == sink.name) {
+                sink.set_filter(sink_config);
+            }
+        }
+    }
 
-        let config = sologger_log_transport::opentelemetry_lib::get_otel_config(&config.opentelemetry_config_location);
-        let _ = sologger_log_transport::opentelemetry_lib::init_logs_opentelemetry(&config);
-    });
+    /// Removes the sinks, unless they were already replaced by the sinks of another load of the plugin
+    fn remove_sinks(&self, sinks: &Arc<Vec<Sink>>) {
+        let installed = self.sinks().is_some_and(|installed| Arc::ptr_eq(&installed, sinks));
+        if installed {
+            self.set_sinks(None);
+        }
+    }
 }
 
-pub fn init_log4rs(log4rs_config_location: &String) -> anyhow::Result<()> {
-    match {
-        init_file(
-            log4rs_config_location,
-            log4rs::config::Deserializers::default(),
-        )
-    } {
-        Ok(_) => {
-            debug!("Logger initialized with logstash successfully")
+impl Log for SinkLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.sinks().is_some_and(|sinks| sinks.iter().any(|sink| sink.accepts(metadata, None)))
+    }
+
+    fn log(&self, record: &Record) {
+        let Some(sinks) = self.sinks() else { return };
+        let program_id = record.key_values().get("program_id".into()).map(|value| value.to_string());
+        let mut owned_record = None;
+        for sink in sinks.iter() {
+            if sink.accepts(record.metadata(), program_id.as_deref()) {
+                let owned_record = owned_record.get_or_insert_with(|| Arc::new(OwnedRecord::new(record)));
+                sink.send(Arc::clone(owned_record));
+            }
         }
-        Err(err) => {
-            error!("init_logstash_logger not initialized! {}", err.to_string())
+    }
+
+    fn flush(&self) {
+        for sink in self.sinks().iter().flat_map(|sinks| sinks.iter()) {
+            sink.flush(FLUSH_TIMEOUT);
         }
+    }
+}
+
+/// Writes each record as a line of JSON
+struct JsonLinesLogger<W: Write + Send> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> JsonLinesLogger<W> {
+    fn new(writer: W) -> Self {
+        JsonLinesLogger { writer: Mutex::new(writer) }
+    }
+}
+
+impl<W: Write + Send> Log for JsonLinesLogger<W> {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let line = json_line(record);
+        let _ = writeln!(self.writer.lock().unwrap(), "{}", line);
+    }
+
+    fn flush(&self) {
+        let _ = self.writer.lock().unwrap().flush();
+    }
+}
+
+/// Formats the record as a JSON object with its `level`, `target` and `timestamp` in milliseconds since the Unix epoch.
+/// Records that are already JSON objects, like the structured logs and events, are extended with these fields rather than nested.
+fn json_line(record: &Record) -> String {
+    let message = record.args().to_string();
+    let mut line = match message.starts_with('{').then(|| serde_json::from_str::<Value>(&message)) {
+        Some(Ok(Value::Object(object))) => object,
+        _ => Map::from_iter([("message".to_string(), Value::from(message))]),
     };
-    Ok(())
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+    line.entry("level").or_insert_with(|| Value::from(record.level().as_str()));
+    line.entry("target").or_insert_with(|| Value::from(record.target()));
+    line.entry("timestamp").or_insert_with(|| Value::from(timestamp));
+    Value::Object(line).to_string()
+}
+
+/// The error returned when a transport fails to initialize
+#[derive(Debug)]
+pub struct TransportError {
+    transport: &'static str,
+    msg: String,
 }
 
+impl std::error::Error for TransportError {}
+
+impl std::fmt::Display for TransportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Unable to initialize the {} transport: {}", self.transport, self.msg)
+    }
+}
+
+impl From<TransportError> for GeyserPluginError {
+    fn from(err: TransportError) -> Self {
+        GeyserPluginError::Custom(Box::new(err))
+    }
+}
 
 #[cfg(test)]
 mod tests {
-    use crate::logger_lib::init_logger;
-    use crate::sologger_config::SologgerConfig;
+    use std::io::Write;
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, Mutex, RwLock};
+    use crossbeam_channel::TrySendError;
+    use log::{Level, LevelFilter, Log, Metadata, Record};
+    use crate::logger_lib::{create_sinks, init_logger, json_line, JsonLinesLogger, Sink, SinkLogger, SinkMessage, TransportRuntime};
+    use crate::sologger_config::{SinkConfig, SologgerConfig, TransportConfig};
     use serde_json::json;
 
     #[test]
@@ -84,6 +562,259 @@ mod tests {
         );
 
         let sologger_config = serde_json::from_value::<SologgerConfig>(config).unwrap();
-        init_logger(&sologger_config);
+        let _ = init_logger(&sologger_config);
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    pub fn test_json_lines() {
+        let buffer = SharedBuffer::default();
+        let logger = JsonLinesLogger::new(buffer.clone());
+        logger.log(&Record::builder()
+            .level(Level::Info)
+            .target("sologger_geyser_plugin::log_processor::info")
+            .args(format_args!("{}", json!({ "program_id": "11111111111111111111111111111111" })))
+            .build());
+        logger.log(&Record::builder().level(Level::Warn).target("sologger_geyser_plugin").args(format_args!("Queue full")).build());
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<serde_json::Value> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["program_id"], "11111111111111111111111111111111");
+        assert_eq!(lines[0]["level"], "INFO");
+        assert_eq!(lines[0]["target"], "sologger_geyser_plugin::log_processor::info");
+        assert_eq!(lines[1]["message"], "Queue full");
+        assert_eq!(lines[1]["level"], "WARN");
+        assert!(lines[1]["timestamp"].is_u64());
+    }
+
+    #[test]
+    pub fn test_json_line_keeps_fields() {
+        let record_json = json!({ "event": "slotStatus", "timestamp": 1700000000450u64 }).to_string();
+        let line = json_line(&Record::builder().level(Level::Info).args(format_args!("{}", record_json)).build());
+        let line: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(line["timestamp"], 1700000000450u64);
+    }
+
+    #[test]
+    pub fn test_create_sinks() {
+        let dir = std::env::temp_dir().join("sologger-logger-lib-tests");
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = json!(
+            {
+                "rpcUrl": "wss://api.mainnet-beta.solana.com",
+                "accountDataNotificationsEnabled": false,
+                "transactionNotificationsEnabled": true,
+                "logProcessorWorkerThreadCount": 1,
+                "transport": { "stdout": {}, "file": { "path": dir.join("sologger.ndjson") } }
+            }
+        );
+        let sologger_config = serde_json::from_value::<SologgerConfig>(config).unwrap();
+        let sinks = create_sinks(&sologger_config, &mut TransportRuntime::default()).unwrap();
+        assert_eq!(sinks.iter().map(|sink| sink.name).collect::<Vec<_>>(), vec!["stdout", "file"]);
+
+        let mut sologger_config = sologger_config;
+        sologger_config.transport.as_mut().unwrap().file.as_mut().unwrap().path = dir.join("missing/sologger.ndjson").display().to_string();
+        let err = create_sinks(&sologger_config, &mut TransportRuntime::default()).err().unwrap();
+        assert!(err.to_string().starts_with("Unable to initialize the file transport"));
+    }
+
+    impl SharedBuffer {
+        fn lines(&self) -> Vec<serde_json::Value> {
+            let output = String::from_utf8(self.0.lock().unwrap().clone()).unwrap();
+            output.lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+        }
+    }
+
+    fn sink_logger(sinks: Vec<Sink>) -> SinkLogger {
+        SinkLogger { sinks: RwLock::new(Some(Arc::new(sinks))) }
+    }
+
+    fn log(logger: &SinkLogger, level: Level, program_id: Option<&str>, message: &str) {
+        let key_values = program_id.map(|program_id| ("program_id", program_id));
+        logger.log(&Record::builder().level(level).target("sologger_geyser_plugin").key_values(&key_values).args(format_args!("{}", message)).build());
+    }
+
+    #[test]
+    pub fn test_sink_filters() {
+        let token_program = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+        let (token_buffer, error_buffer) = (SharedBuffer::default(), SharedBuffer::default());
+        let token_config = SinkConfig { programs: vec![token_program.to_string()], ..Default::default() };
+        let error_config = SinkConfig { level: Some("error".to_string()), ..Default::default() };
+        let logger = sink_logger(vec![
+            Sink::new("file", Arc::new(JsonLinesLogger::new(token_buffer.clone())), LevelFilter::Info, &token_config).unwrap(),
+            Sink::new("otel", Arc::new(JsonLinesLogger::new(error_buffer.clone())), LevelFilter::Info, &error_config).unwrap(),
+        ]);
+
+        log(&logger, Level::Info, Some(token_program), "token info");
+        log(&logger, Level::Info, Some("11111111111111111111111111111111"), "system info");
+        log(&logger, Level::Error, Some("11111111111111111111111111111111"), "system error");
+        log(&logger, Level::Warn, None, "slot warn");
+        log(&logger, Level::Debug, Some(token_program), "token debug");
+        logger.flush();
+
+        let messages = |buffer: &SharedBuffer| buffer.lines().iter().map(|line| line["message"].as_str().unwrap().to_string()).collect::<Vec<_>>();
+        assert_eq!(messages(&token_buffer), vec!["token info", "slot warn"]);
+        assert_eq!(messages(&error_buffer), vec!["system error"]);
+    }
+
+    #[test]
+    pub fn test_apply_filters() {
+        let token_program = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+        let buffer = SharedBuffer::default();
+        let logger = sink_logger(vec![Sink::new("stdout", Arc::new(JsonLinesLogger::new(buffer.clone())), LevelFilter::Info, &SinkConfig::default()).unwrap()]);
+        log(&logger, Level::Debug, Some(token_program), "token debug");
+
+        logger.apply_filters(&TransportConfig {
+            stdout: Some(SinkConfig { programs: vec![token_program.to_string()], level: Some("debug".to_string()), ..Default::default() }),
+            ..Default::default()
+        });
+        assert_eq!(logger.max_level(), LevelFilter::Debug);
+        log(&logger, Level::Debug, Some(token_program), "token debug after reload");
+        log(&logger, Level::Info, Some("11111111111111111111111111111111"), "system info after reload");
+
+        // Without a level, the sink falls back to the level of its logger
+        logger.apply_filters(&TransportConfig { stdout: Some(SinkConfig::default()), ..Default::default() });
+        log(&logger, Level::Debug, Some(token_program), "token debug after second reload");
+        log(&logger, Level::Info, Some("11111111111111111111111111111111"), "system info after second reload");
+        logger.flush();
+
+        let messages: Vec<String> = buffer.lines().iter().map(|line| line["message"].as_str().unwrap().to_string()).collect();
+        assert_eq!(messages, vec!["token debug after reload", "system info after second reload"]);
+    }
+
+    #[test]
+    pub fn test_shutdown_stops_sinks() {
+        let buffer = SharedBuffer::default();
+        let sinks = Arc::new(vec![Sink::new("file", Arc::new(JsonLinesLogger::new(buffer.clone())), LevelFilter::Info, &SinkConfig::default()).unwrap()]);
+        let logger = SinkLogger { sinks: RwLock::new(Some(Arc::clone(&sinks))) };
+        let mut transport_runtime = TransportRuntime::default();
+        transport_runtime.sinks = Some(Arc::clone(&sinks));
+        log(&logger, Level::Info, None, "transaction");
+
+        // The queued logs are sent before the thread of the sink is stopped and joined
+        transport_runtime.shutdown();
+        assert_eq!(buffer.lines().len(), 1);
+        assert!(sinks[0].thread.lock().unwrap().is_none());
+        assert!(matches!(sinks[0].sender.try_send(SinkMessage::Flush(crossbeam_channel::bounded(1).0)), Err(TrySendError::Disconnected(_))));
+    }
+
+    /// A logger that records the program_id of the records it receives
+    #[derive(Default)]
+    struct ProgramIdLogger {
+        program_ids: Mutex<Vec<Option<String>>>,
+    }
+
+    impl Log for ProgramIdLogger {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            let program_id = record.key_values().get("program_id".into()).map(|value| value.to_string());
+            self.program_ids.lock().unwrap().push(program_id);
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    pub fn test_sink_key_values() {
+        let token_program = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+        let program_id_logger = Arc::new(ProgramIdLogger::default());
+        let logger = sink_logger(vec![Sink::new("otel", program_id_logger.clone(), LevelFilter::Info, &SinkConfig::default()).unwrap()]);
+        log(&logger, Level::Info, Some(token_program), "token info");
+        log(&logger, Level::Info, None, "slot info");
+        logger.flush();
+
+        assert_eq!(*program_id_logger.program_ids.lock().unwrap(), vec![Some(token_program.to_string()), None]);
+    }
+
+    /// A logger that blocks while the test holds its gate
+    struct BlockingLogger {
+        gate: Mutex<()>,
+    }
+
+    impl Log for BlockingLogger {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, _record: &Record) {
+            drop(self.gate.lock().unwrap());
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    pub fn test_slow_sink() {
+        let buffer = SharedBuffer::default();
+        let slow_logger = Arc::new(BlockingLogger { gate: Mutex::new(()) });
+        let slow_config = SinkConfig { max_queued_logs: Some(1), ..Default::default() };
+        let logger = sink_logger(vec![
+            Sink::new("log4rs", slow_logger.clone(), LevelFilter::Info, &slow_config).unwrap(),
+            Sink::new("file", Arc::new(JsonLinesLogger::new(buffer.clone())), LevelFilter::Info, &SinkConfig::default()).unwrap(),
+        ]);
+
+        let gate = slow_logger.gate.lock().unwrap();
+        for _ in 0..10 {
+            log(&logger, Level::Info, None, "transaction");
+        }
+        let dropped = logger.sinks().unwrap()[0].dropped.load(Ordering::Relaxed);
+        assert!((8..=9).contains(&dropped), "dropped {}", dropped);
+        drop(gate);
+
+        logger.flush();
+        assert_eq!(buffer.lines().len(), 10);
+        assert_eq!(logger.sinks().unwrap()[1].dropped.load(Ordering::Relaxed), 0);
+    }
+
+    #[cfg(feature = "enable_otel")]
+    #[test]
+    pub fn test_otel_sink() {
+        let dir = std::env::temp_dir().join("sologger-logger-lib-tests");
+        std::fs::create_dir_all(&dir).unwrap();
+        let otel_config_file = dir.join("opentelemetry-config.json");
+        let write_otel_config = |endpoint: &str| std::fs::write(&otel_config_file, json!({
+            "logConfig": { "service.name": "sologger-geyser-plugin" },
+            "endpoint": endpoint,
+            "metricsEndpoint": "",
+            "tracesEndpoint": "",
+            "logLevel": "info"
+        }).to_string()).unwrap();
+        let sologger_config = serde_json::from_value::<SologgerConfig>(json!({
+            "rpcUrl": "wss://api.mainnet-beta.solana.com",
+            "opentelemetryConfigLocation": otel_config_file,
+            "accountDataNotificationsEnabled": false,
+            "transactionNotificationsEnabled": true,
+            "logProcessorWorkerThreadCount": 1,
+            "transport": { "otel": {} }
+        })).unwrap();
+
+        write_otel_config("not an endpoint");
+        let err = create_sinks(&sologger_config, &mut TransportRuntime::default()).err().unwrap();
+        assert!(err.to_string().starts_with("Unable to initialize the otel transport"), "{}", err);
+
+        // Nothing listens on the endpoint, the export fails without blocking the shutdown
+        write_otel_config("http://127.0.0.1:9");
+        let mut transport_runtime = TransportRuntime::default();
+        let logger = sink_logger(create_sinks(&sologger_config, &mut transport_runtime).unwrap());
+        log(&logger, Level::Info, None, "transaction");
+        logger.flush();
+        transport_runtime.shutdown();
+        assert!(transport_runtime.otel.is_none());
     }
 }
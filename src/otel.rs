@@ -0,0 +1,276 @@
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use log::debug;
+use opentelemetry::metrics::{Counter, Histogram, Meter, MeterProvider};
+use opentelemetry::{global, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::logs::LoggerProvider;
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use opentelemetry_sdk::trace::TracerProvider;
+use opentelemetry_sdk::{runtime, Resource};
+use sologger_log_transport::opentelemetry_config::OpentelemetryConfig;
+use tokio::runtime::Handle;
+use tokio::sync::oneshot;
+
+/// A Tokio runtime running on a dedicated thread, on which the OpenTelemetry exporters send their batches.
+/// It lives as long as the plugin, and flushes and shuts down the providers registered with it when it is dropped.
+pub struct OtelRuntime {
+    handle: Handle,
+    stop: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+    logger_provider: Option<LoggerProvider>,
+    tracer_provider: Option<TracerProvider>,
+    meter_provider: Option<SdkMeterProvider>,
+    program_metrics: Option<Arc<ProgramMetrics>>,
+}
+
+impl OtelRuntime {
+    /// Starts the runtime thread
+    pub fn start() -> Result<Self, String> {
+        let (handle_sender, handle_receiver) = std::sync::mpsc::channel();
+        let (stop, stopped) = oneshot::channel::<()>();
+        let thread = std::thread::Builder::new()
+            .name("sologger-otel".to_string())
+            .spawn(move || {
+                let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                    Ok(runtime) => runtime,
+                    Err(err) => {
+                        let _ = handle_sender.send(Err(err.to_string()));
+                        return;
+                    }
+                };
+                let _ = handle_sender.send(Ok(runtime.handle().clone()));
+                // The exporter tasks run until the runtime is stopped, or the OtelRuntime is dropped
+                let _ = runtime.block_on(stopped);
+            })
+            .map_err(|err| err.to_string())?;
+        let handle = handle_receiver.recv().map_err(|err| err.to_string())??;
+        Ok(OtelRuntime { handle, stop: Some(stop), thread: Some(thread), logger_provider: None, tracer_provider: None, meter_provider: None, program_metrics: None })
+    }
+
+    /// Returns the handle of the runtime, which must be entered to create the exporters
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Registers the logger provider, so it is shut down with the runtime
+    pub fn set_logger_provider(&mut self, logger_provider: LoggerProvider) {
+        self.logger_provider = Some(logger_provider);
+    }
+
+    /// Starts the traces exporter on the tracesEndpoint, and installs it as the global tracer provider
+    pub fn start_traces(&mut self, config: &OpentelemetryConfig) -> Result<(), String> {
+        if config.traces_endpoint.is_empty() {
+            return Err("tracesEndpoint is not set".to_string());
+        }
+        let tracer_provider = {
+            let _guard = self.handle.enter();
+            opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_trace_config(opentelemetry_sdk::trace::Config::default().with_resource(resource(config)))
+                .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(&config.traces_endpoint))
+                .install_batch(runtime::Tokio)
+                .map_err(|err| err.to_string())?
+        };
+        global::set_tracer_provider(tracer_provider.clone());
+        self.tracer_provider = Some(tracer_provider);
+        Ok(())
+    }
+
+    /// Starts the metrics exporter on the metricsEndpoint, exporting every `export_interval`, and installs it as the global meter provider
+    pub fn start_metrics(&mut self, config: &OpentelemetryConfig, export_interval: Duration) -> Result<(), String> {
+        if config.metrics_endpoint.is_empty() {
+            return Err("metricsEndpoint is not set".to_string());
+        }
+        let meter_provider = {
+            let _guard = self.handle.enter();
+            opentelemetry_otlp::new_pipeline()
+                .metrics(runtime::Tokio)
+                .with_resource(resource(config))
+                .with_period(export_interval)
+                .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(&config.metrics_endpoint))
+                .build()
+                .map_err(|err| err.to_string())?
+        };
+        global::set_meter_provider(meter_provider.clone());
+        let program_metrics = Arc::new(ProgramMetrics::new(&meter_provider.meter(METER_NAME)));
+        *PROGRAM_METRICS.write().unwrap() = Some(Arc::clone(&program_metrics));
+        self.program_metrics = Some(program_metrics);
+        self.meter_provider = Some(meter_provider);
+        Ok(())
+    }
+}
+
+impl Drop for OtelRuntime {
+    fn drop(&mut self) {
+        // The providers export their remaining batches on the runtime, so they are shut down before it is stopped
+        if let Some(logger_provider) = self.logger_provider.take() {
+            if let Err(err) = logger_provider.shutdown() {
+                debug!("Error shutting down the OpenTelemetry logger provider: {}", err);
+            }
+        }
+        if let Some(tracer_provider) = self.tracer_provider.take() {
+            global::set_tracer_provider(opentelemetry::trace::noop::NoopTracerProvider::new());
+            if let Err(err) = tracer_provider.shutdown() {
+                debug!("Error shutting down the OpenTelemetry tracer provider: {}", err);
+            }
+        }
+        if let Some(program_metrics) = self.program_metrics.take() {
+            let mut installed = PROGRAM_METRICS.write().unwrap();
+            if installed.as_ref().is_some_and(|installed| Arc::ptr_eq(installed, &program_metrics)) {
+                *installed = None;
+            }
+        }
+        if let Some(meter_provider) = self.meter_provider.take() {
+            global::set_meter_provider(opentelemetry::metrics::noop::NoopMeterProvider::new());
+            if let Err(err) = meter_provider.shutdown() {
+                debug!("Error shutting down the OpenTelemetry meter provider: {}", err);
+            }
+        }
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The name of the meter of the program metrics
+pub const METER_NAME: &str = "sologger-geyser-plugin";
+
+/// The instruments of the program metrics, see [crate::log_processor::record_metrics], and of the tasks dropped by [crate::task_queue::TaskQueue]
+pub struct ProgramMetrics {
+    pub(crate) invocations: Counter<u64>,
+    pub(crate) failures: Counter<u64>,
+    pub(crate) compute_units: Histogram<u64>,
+    pub(crate) transaction_logs: Histogram<u64>,
+    pub(crate) tasks_dropped: Counter<u64>,
+}
+
+impl ProgramMetrics {
+    pub fn new(meter: &Meter) -> Self {
+        ProgramMetrics {
+            invocations: meter
+                .u64_counter("solana.program.invocations")
+                .with_description("The number of invocations of the program")
+                .init(),
+            failures: meter
+                .u64_counter("solana.program.failures")
+                .with_description("The number of failed invocations of the program")
+                .init(),
+            compute_units: meter
+                .u64_histogram("solana.program.compute_units")
+                .with_description("The compute units consumed by each invocation of the program")
+                .with_unit("{compute_unit}")
+                .with_boundaries(vec![1_000.0, 5_000.0, 10_000.0, 25_000.0, 50_000.0, 100_000.0, 200_000.0, 400_000.0, 800_000.0, 1_400_000.0])
+                .init(),
+            transaction_logs: meter
+                .u64_histogram("solana.transaction.logs")
+                .with_description("The number of log messages of each transaction")
+                .with_unit("{log}")
+                .with_boundaries(vec![5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1_000.0])
+                .init(),
+            tasks_dropped: meter
+                .u64_counter("solana.plugin.tasks_dropped")
+                .with_description("The number of tasks dropped because the task queue was full")
+                .with_unit("{task}")
+                .init(),
+        }
+    }
+}
+
+/// The program metrics of the meter provider of the running plugin, replaced when the plugin is reloaded
+static PROGRAM_METRICS: RwLock<Option<Arc<ProgramMetrics>>> = RwLock::new(None);
+
+/// Returns the program metrics of the meter provider started by [OtelRuntime::start_metrics], or None if the metrics are not started
+pub fn program_metrics() -> Option<Arc<ProgramMetrics>> {
+    PROGRAM_METRICS.read().unwrap().clone()
+}
+
+/// Reads the OpenTelemetry config at opentelemetryConfigLocation
+pub fn read_otel_config(opentelemetry_config_location: &str) -> Result<OpentelemetryConfig, String> {
+    let contents = std::fs::read_to_string(opentelemetry_config_location)
+        .map_err(|err| format!("{}: {}", opentelemetry_config_location, err))?;
+    serde_json::from_str(&contents).map_err(|err| format!("{}: {}", opentelemetry_config_location, err))
+}
+
+/// Returns the resource describing this plugin, with the attributes of the logConfig of the OpenTelemetry config
+pub fn resource(config: &OpentelemetryConfig) -> Resource {
+    Resource::new(config.log_config.iter().map(|(key, value)| KeyValue::new(key.clone(), value.clone())))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::mpsc::{channel, Receiver, Sender};
+    use std::sync::Mutex;
+    use opentelemetry_proto::tonic::collector::metrics::v1::metrics_service_server::{MetricsService, MetricsServiceServer};
+    use opentelemetry_proto::tonic::collector::metrics::v1::{ExportMetricsServiceRequest, ExportMetricsServiceResponse};
+    use opentelemetry_proto::tonic::collector::trace::v1::trace_service_server::{TraceService, TraceServiceServer};
+    use opentelemetry_proto::tonic::collector::trace::v1::{ExportTraceServiceRequest, ExportTraceServiceResponse};
+    use tokio::runtime::Handle;
+    use tonic::transport::Server;
+    use crate::otel::OtelRuntime;
+
+    /// Serializes the tests that install the global tracer and meter providers
+    pub(crate) static GLOBAL_PROVIDERS: Mutex<()> = Mutex::new(());
+
+    #[test]
+    pub fn test_runtime_thread() {
+        let runtime = OtelRuntime::start().unwrap();
+        let (sender, receiver) = channel();
+        runtime.handle().spawn(async move { sender.send(std::thread::current().name().map(str::to_string)).unwrap() });
+        assert_eq!(receiver.recv().unwrap().as_deref(), Some("sologger-otel"));
+
+        // Dropping the runtime stops and joins its thread
+        drop(runtime);
+    }
+
+    /// The export requests received by the collector stand-in
+    pub(crate) struct CollectorRequests {
+        pub(crate) traces: Receiver<ExportTraceServiceRequest>,
+        pub(crate) metrics: Receiver<ExportMetricsServiceRequest>,
+    }
+
+    struct TraceCollector {
+        sender: Sender<ExportTraceServiceRequest>,
+    }
+
+    #[tonic::async_trait]
+    impl TraceService for TraceCollector {
+        async fn export(&self, request: tonic::Request<ExportTraceServiceRequest>) -> Result<tonic::Response<ExportTraceServiceResponse>, tonic::Status> {
+            let _ = self.sender.send(request.into_inner());
+            Ok(tonic::Response::new(ExportTraceServiceResponse { partial_success: None }))
+        }
+    }
+
+    struct MetricsCollector {
+        sender: Sender<ExportMetricsServiceRequest>,
+    }
+
+    #[tonic::async_trait]
+    impl MetricsService for MetricsCollector {
+        async fn export(&self, request: tonic::Request<ExportMetricsServiceRequest>) -> Result<tonic::Response<ExportMetricsServiceResponse>, tonic::Status> {
+            let _ = self.sender.send(request.into_inner());
+            Ok(tonic::Response::new(ExportMetricsServiceResponse { partial_success: None }))
+        }
+    }
+
+    /// Serves an OTLP collector stand-in on the runtime, and returns its endpoint and the export requests it receives
+    pub(crate) fn start_collector(runtime: &Handle) -> (String, CollectorRequests) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let (traces_sender, traces) = channel();
+        let (metrics_sender, metrics) = channel();
+        let _guard = runtime.enter();
+        let incoming = tokio_stream::wrappers::TcpListenerStream::new(tokio::net::TcpListener::from_std(listener).unwrap());
+        runtime.spawn(Server::builder()
+            .add_service(TraceServiceServer::new(TraceCollector { sender: traces_sender }))
+            .add_service(MetricsServiceServer::new(MetricsCollector { sender: metrics_sender }))
+            .serve_with_incoming(incoming));
+        (endpoint, CollectorRequests { traces, metrics })
+    }
+}
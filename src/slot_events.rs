@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use agave_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+use serde::Serialize;
+
+/// The number of slots below the latest root to remember the first notification of
+const MAX_TRACKED_SLOTS: u64 = 512;
+
+/// A slot status notification, emitted as JSON on the `sologger_geyser_plugin::geyser_plugin_sologger::update_slot_status` log target
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SlotStatusEvent {
+    event: &'static str,
+    slot: u64,
+    parent: Option<u64>,
+    status: &'static str,
+    /// When the status was notified, in milliseconds since the Unix epoch
+    timestamp: u64,
+    /// The time since the first status notification of the slot, in milliseconds
+    since_first_seen_millis: u64,
+}
+
+impl SlotStatusEvent {
+    pub(crate) fn new(slot: u64, parent: Option<u64>, status: &SlotStatus, since_first_seen: Duration) -> Self {
+        SlotStatusEvent {
+            event: "slotStatus",
+            slot,
+            parent,
+            status: status_name(status),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+            since_first_seen_millis: since_first_seen.as_millis() as u64,
+        }
+    }
+}
+
+fn status_name(status: &SlotStatus) -> &'static str {
+    match status {
+        SlotStatus::Processed => "processed",
+        SlotStatus::Confirmed => "confirmed",
+        SlotStatus::Rooted => "rooted",
+    }
+}
+
+/// Remembers when each recent slot was first notified
+pub(crate) struct SlotTracker {
+    first_seen: Mutex<BTreeMap<u64, Instant>>,
+}
+
+impl SlotTracker {
+    pub(crate) fn new() -> Self {
+        SlotTracker { first_seen: Mutex::new(BTreeMap::new()) }
+    }
+
+    /// Records a status notification of the slot and returns the time since its first notification
+    pub(crate) fn observe(&self, slot: u64, status: &SlotStatus) -> Duration {
+        let mut first_seen = self.first_seen.lock().unwrap();
+        let since_first_seen = first_seen.entry(slot).or_insert_with(Instant::now).elapsed();
+        if *status == SlotStatus::Rooted {
+            *first_seen = first_seen.split_off(&slot.saturating_sub(MAX_TRACKED_SLOTS));
+        }
+        since_first_seen
+    }
+}
+
+impl Default for SlotTracker {
+    fn default() -> Self {
+        SlotTracker::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use agave_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+    use crate::slot_events::{SlotStatusEvent, SlotTracker, MAX_TRACKED_SLOTS};
+
+    #[test]
+    fn test_slot_status_event_json() {
+        let event = SlotStatusEvent::new(2, Some(1), &SlotStatus::Confirmed, Duration::from_millis(450));
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "slotStatus");
+        assert_eq!(json["slot"], 2);
+        assert_eq!(json["parent"], 1);
+        assert_eq!(json["status"], "confirmed");
+        assert_eq!(json["sinceFirstSeenMillis"], 450);
+        assert!(json["timestamp"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_slot_tracker() {
+        let tracker = SlotTracker::new();
+        assert!(tracker.observe(1, &SlotStatus::Processed) < Duration::from_millis(1));
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(tracker.observe(1, &SlotStatus::Confirmed) >= Duration::from_millis(5));
+
+        tracker.observe(MAX_TRACKED_SLOTS + 2, &SlotStatus::Rooted);
+        assert!(tracker.observe(1, &SlotStatus::Rooted) < Duration::from_millis(1));
+    }
+}
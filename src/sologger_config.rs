@@ -1,9 +1,11 @@
+use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
+#[cfg(test)]
 use serde_json::json;
 
-/// This is the main configuration file for sologger. The location of this file is specified by the `SOLOGGER_APP_CONFIG_LOC` environment variable or as the first argument via the cargo run command.
+/// This is the main configuration file for sologger. The location of this file is specified by the geyser plugin config (`sologgerConfigPath`), or it can be embedded in it (`sologger`). The `SOLOGGER_APP_CONFIG_LOC` environment variable overrides both.
 #[derive(Serialize, Deserialize, Default, Clone, PartialEq, Eq, Debug)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct SologgerConfig {
     /// The location of the log4rs config file
     #[serde(default)]
@@ -13,7 +15,22 @@ pub struct SologgerConfig {
     pub opentelemetry_config_location: String,
     /// The URL of the RPC endpoint to connect to
     pub rpc_url: String,
-    /// The measure of the network confirmation and stake levels on a particular block.
+    /// The programs to produce structured logs for. If omitted, no program logs are emitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub programs_selector: Option<ProgramsSelectorConfig>,
+    /// The accounts to emit update events for, by pubkey or owner program. If omitted, no account events are emitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub accounts_selector: Option<AccountsSelectorConfig>,
+    /// Anchor IDL files keyed by program ID. The data of accounts owned by these programs is decoded with the IDL in the account events,
+    /// and the events they emit in `Program data:` logs are decoded and added to their structured logs.
+    /// Relative paths are resolved against the directory of the sologger config file.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    #[serde(default)]
+    pub idls: HashMap<String, String>,
+    /// The commitment a slot must reach before the logs of its transactions are emitted: processed (default), confirmed or finalized.
+    /// Logs are held back until the slot status notifications report the commitment.
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
     pub commitment_level: Option<String>,
@@ -25,7 +42,280 @@ pub struct SologgerConfig {
     /// Determines whether transaction notifications are enabled or not
     pub transaction_notifications_enabled: bool,
     /// The number of worker threads for log processing. These threads are used for parsing the unstructured logs into structured logs and sending them to the logger. The number needed depend on your validator and use case.
-    pub log_processor_worker_thread_count: u8
+    pub log_processor_worker_thread_count: u8,
+    /// The maximum number of transactions waiting to be processed by the worker threads. Defaults to 10000. Changes to this value require a validator restart.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub max_queued_tasks: Option<usize>,
+    /// What to do with new transactions when maxQueuedTasks is reached. Defaults to dropping the newest transaction.
+    #[serde(default)]
+    pub queue_overflow_policy: QueueOverflowPolicy,
+    /// How long, in milliseconds, to keep processing queued transactions when the plugin is unloaded. Transactions still queued after this deadline are discarded. Defaults to 5000.
+    #[serde(default = "default_drain_timeout_millis")]
+    pub drain_timeout_millis: u64,
+    /// How often, in seconds, to check the sologger config file for changes. Changes are applied without restarting the validator, except for the fields that are only read when the plugin is loaded, which are logged as requiring a restart. Set to 0 to disable hot reloading.
+    #[serde(default)]
+    pub config_reload_interval_secs: u64,
+    /// How long, in milliseconds, to hold back the logs of a transaction until the block metadata of its slot is received, so the logs carry the block time. Logs are emitted without a block time after this timeout. Set to 0 to emit logs immediately. Defaults to 2000.
+    #[serde(default = "default_block_time_timeout_millis")]
+    pub block_time_timeout_millis: u64,
+    /// Set to true to emit the logs of transactions on forks that were abandoned before reaching the commitmentLevel, tagged with `"orphaned": true`. Otherwise, they are discarded.
+    #[serde(default)]
+    pub emit_orphaned_logs: bool,
+    /// Which slot status notifications to emit as JSON events. All statuses are emitted by default.
+    #[serde(default)]
+    pub slot_status_events: SlotStatusEventsConfig,
+    /// Set to true to add a summary of the selected program invocations, errors and compute units to each block event. The block event is then emitted once the logs of all the block's transactions have been processed.
+    #[serde(default)]
+    pub block_program_rollup: bool,
+    /// Which fields of the transaction to add to each structured log, under `transaction`. All fields are disabled by default.
+    #[serde(default)]
+    pub transaction_fields: TransactionFieldsConfig,
+    /// Set to true to emit the instruction tree of each selected transaction, with the inner instructions invoked through CPI and the log lines of each invocation.
+    #[serde(default)]
+    pub cpi_tree: bool,
+    /// Set to true to export each selected transaction as an OpenTelemetry trace to the tracesEndpoint of the OpenTelemetry config, with a span per program invocation. Requires the enable_otel feature.
+    /// Changes to this value require a validator restart.
+    #[serde(default)]
+    pub traces: bool,
+    /// Set to true to export the invocations, failures and compute units of the selected programs, and the number of logs per transaction, as OpenTelemetry metrics to the metricsEndpoint of the OpenTelemetry config. Requires the enable_otel feature.
+    /// Changes to this value require a validator restart.
+    #[serde(default)]
+    pub metrics: bool,
+    /// How often, in seconds, to export the metrics. Defaults to 60.
+    #[serde(default = "default_metrics_export_interval_secs")]
+    pub metrics_export_interval_secs: u64,
+    /// The sinks to send the logs to. If omitted, the sinks are chosen by the enabled cargo features, see [SologgerConfig::transport].
+    /// Changes to the level and programs filters of the sinks are applied when the config is reloaded, other changes require a validator restart.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub transport: Option<TransportConfig>,
+}
+
+impl SologgerConfig {
+    /// Returns the configured transport. Without a transport section, a single transport of the enabled cargo features is used, so the logs are not shipped twice:
+    /// logstash for enable_logstash, including the default build with both features, otherwise log4rs and otel for enable_otel.
+    pub fn transport(&self) -> TransportConfig {
+        match &self.transport {
+            Some(transport) => transport.clone(),
+            None => {
+                let logstash = cfg!(feature = "enable_logstash");
+                let otel = cfg!(feature = "enable_otel") && !logstash;
+                TransportConfig {
+                    log4rs: otel.then(SinkConfig::default),
+                    logstash: logstash.then(SinkConfig::default),
+                    otel: otel.then(SinkConfig::default),
+                    ..Default::default()
+                }
+            }
+        }
+    }
+}
+
+fn default_drain_timeout_millis() -> u64 {
+    5000
+}
+
+fn default_block_time_timeout_millis() -> u64 {
+    2000
+}
+
+fn default_metrics_export_interval_secs() -> u64 {
+    60
+}
+
+/// Determines which transactions are dropped when the task queue is full, for example because the log transport is stalled
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum QueueOverflowPolicy {
+    /// Drop the transaction that could not be queued
+    #[default]
+    DropNewest,
+    /// Drop the oldest queued transaction to make room for the new one
+    DropOldest,
+    /// Drop the new transaction, unless it failed. A failed transaction replaces the oldest queued transaction instead.
+    DropNonErrorFirst,
+    /// Wait at most `timeoutMicros` for room in the queue before dropping the new transaction. This blocks the validator while waiting.
+    #[serde(rename_all = "camelCase")]
+    Block { timeout_micros: u64 },
+}
+
+impl QueueOverflowPolicy {
+    /// Returns the name of the policy in the sologger config
+    pub fn name(&self) -> &'static str {
+        match self {
+            QueueOverflowPolicy::DropNewest => "dropNewest",
+            QueueOverflowPolicy::DropOldest => "dropOldest",
+            QueueOverflowPolicy::DropNonErrorFirst => "dropNonErrorFirst",
+            QueueOverflowPolicy::Block { .. } => "block",
+        }
+    }
+}
+
+/// The account pubkeys and owner program IDs to select, as base58 strings. Use "*" in accounts to select all accounts.
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AccountsSelectorConfig {
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    #[serde(default)]
+    pub owners: Vec<String>,
+}
+
+/// The sinks the logs are sent to. Each sink has its own queue and filters, so a slow sink does not hold back the others.
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TransportConfig {
+    /// Sends the logs to the appenders of the log4rs config at log4rsConfigLocation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub log4rs: Option<SinkConfig>,
+    /// Like log4rs, with support for the logstash appender. Requires the enable_logstash feature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub logstash: Option<SinkConfig>,
+    /// Sends the logs to the OpenTelemetry collector of the config at opentelemetryConfigLocation. Requires the enable_otel feature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub otel: Option<SinkConfig>,
+    /// Writes the logs to stdout as JSON lines
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub stdout: Option<SinkConfig>,
+    /// Appends the logs to a file as JSON lines
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub file: Option<FileTransportConfig>,
+}
+
+impl TransportConfig {
+    /// Returns the name and config of each configured sink
+    pub fn sinks(&self) -> Vec<(&'static str, SinkConfig)> {
+        [
+            ("log4rs", self.log4rs.clone()),
+            ("logstash", self.logstash.clone()),
+            ("otel", self.otel.clone()),
+            ("stdout", self.stdout.clone()),
+            ("file", self.file.as_ref().map(FileTransportConfig::sink)),
+        ]
+            .into_iter()
+            .filter_map(|(name, sink)| Some((name, sink?)))
+            .collect()
+    }
+}
+
+/// The filters of a sink and the size of its queue
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SinkConfig {
+    /// Only send the logs of these program IDs. Logs that do not belong to a program invocation, like the slot and block events, are always sent. All programs by default.
+    #[serde(default)]
+    pub programs: Vec<String>,
+    /// The minimum severity of the logs to send: error, warn, info, debug or trace. Defaults to the level of the log4rs or OpenTelemetry config, and to info for stdout and file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub level: Option<String>,
+    /// The maximum number of logs waiting to be sent. Logs are dropped while the queue is full. Defaults to 10000.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub max_queued_logs: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct FileTransportConfig {
+    /// The file to append the logs to. It is created if it does not exist.
+    pub path: String,
+    /// See [SinkConfig::programs]
+    #[serde(default)]
+    pub programs: Vec<String>,
+    /// See [SinkConfig::level]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub level: Option<String>,
+    /// See [SinkConfig::max_queued_logs]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub max_queued_logs: Option<usize>,
+}
+
+impl FileTransportConfig {
+    /// Returns the filters and queue size of the file sink
+    pub fn sink(&self) -> SinkConfig {
+        SinkConfig { programs: self.programs.clone(), level: self.level.clone(), max_queued_logs: self.max_queued_logs }
+    }
+}
+
+/// Enables the slot status events per status
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields, default)]
+pub struct SlotStatusEventsConfig {
+    pub processed: bool,
+    pub confirmed: bool,
+    pub rooted: bool,
+}
+
+impl Default for SlotStatusEventsConfig {
+    fn default() -> Self {
+        SlotStatusEventsConfig { processed: true, confirmed: true, rooted: true }
+    }
+}
+
+/// Enables the transaction fields added to the structured logs per field
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields, default)]
+pub struct TransactionFieldsConfig {
+    pub fee: bool,
+    pub compute_units_consumed: bool,
+    /// The index of the transaction in the block
+    pub index: bool,
+    pub fee_payer: bool,
+    /// The lamport balances of the transaction's accounts before and after the transaction
+    pub balances: bool,
+    /// The token balances of the transaction's accounts before and after the transaction
+    pub token_balances: bool,
+    /// The addresses loaded from address lookup tables
+    pub loaded_addresses: bool,
+}
+
+impl TransactionFieldsConfig {
+    /// Returns true if any field is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.fee || self.compute_units_consumed || self.index || self.fee_payer
+            || self.balances || self.token_balances || self.loaded_addresses
+    }
+}
+
+/// The program IDs to select, as base58 strings. Use "*" to select all programs.
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ProgramsSelectorConfig {
+    #[serde(default)]
+    pub programs: Vec<String>,
+    /// Human-readable names for program IDs, for example `"token": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"`. Aliased programs are selected as well, and their name is added to the emitted logs as `programName`.
+    #[serde(default)]
+    pub aliases: HashMap<String, String>,
+}
+
+impl ProgramsSelectorConfig {
+    /// Returns all selected program IDs, including the aliased ones
+    pub fn program_ids(&self) -> Vec<String> {
+        let mut program_ids = self.programs.clone();
+        for program_id in self.aliases.values() {
+            if !program_ids.contains(program_id) {
+                program_ids.push(program_id.clone());
+            }
+        }
+        program_ids
+    }
+
+    /// Returns the aliases keyed by program ID
+    pub fn program_names(&self) -> HashMap<String, String> {
+        self.aliases
+            .iter()
+            .map(|(name, program_id)| (program_id.clone(), name.clone()))
+            .collect()
+    }
 }
 
 #[test]
@@ -55,8 +345,16 @@ pub fn test_deserialize() {
         sologger_config.log4rs_config_location,
         "./config/log4rs-config.yml"
     );
-    assert_eq!(sologger_config.all_with_votes, false);
+    assert!(!sologger_config.all_with_votes);
     assert_eq!(sologger_config.commitment_level, None);
+    assert_eq!(sologger_config.config_reload_interval_secs, 0);
+    assert_eq!(sologger_config.queue_overflow_policy, QueueOverflowPolicy::DropNewest);
+    assert_eq!(sologger_config.drain_timeout_millis, 5000);
+    assert_eq!(sologger_config.block_time_timeout_millis, 2000);
+    assert_eq!(sologger_config.metrics_export_interval_secs, 60);
+    assert_eq!(sologger_config.slot_status_events, SlotStatusEventsConfig::default());
+    assert!(!sologger_config.transaction_fields.is_enabled());
+    assert!(sologger_config.transport.is_none());
 }
 
 #[test]
@@ -73,7 +371,26 @@ pub fn test_deserialize_all() {
             "commitmentLevel": "recent",
             "accountDataNotificationsEnabled": true,
             "transactionNotificationsEnabled": true,
-            "logProcessorWorkerThreadCount": 2
+            "logProcessorWorkerThreadCount": 2,
+            "maxQueuedTasks": 500,
+            "queueOverflowPolicy": { "block": { "timeoutMicros": 250 } },
+            "drainTimeoutMillis": 1000,
+            "configReloadIntervalSecs": 10,
+            "blockTimeTimeoutMillis": 0,
+            "emitOrphanedLogs": true,
+            "slotStatusEvents": { "processed": false },
+            "blockProgramRollup": true,
+            "accountsSelector": { "owners": ["TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"] },
+            "idls": { "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin": "./idls/counter.json" },
+            "transactionFields": { "fee": true, "computeUnitsConsumed": true },
+            "cpiTree": true,
+            "traces": true,
+            "metrics": true,
+            "metricsExportIntervalSecs": 15,
+            "transport": {
+                "stdout": { "level": "error" },
+                "file": { "path": "./sologger.ndjson", "programs": ["TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"], "maxQueuedLogs": 100 }
+            }
         }
     );
 
@@ -87,9 +404,116 @@ pub fn test_deserialize_all() {
         sologger_config.opentelemetry_config_location,
         "./config/opentelemetry-config.json"
     );
-    assert_eq!(sologger_config.all_with_votes, true);
-    assert_eq!(sologger_config.commitment_level.unwrap(), "recent");
-    assert_eq!(sologger_config.account_data_notifications_enabled, true);
-    assert_eq!(sologger_config.transaction_notifications_enabled, true);
+    assert!(sologger_config.all_with_votes);
+    assert_eq!(sologger_config.commitment_level.as_deref(), Some("recent"));
+    assert!(sologger_config.account_data_notifications_enabled);
+    assert!(sologger_config.transaction_notifications_enabled);
     assert_eq!(sologger_config.log_processor_worker_thread_count, 2);
+    assert_eq!(sologger_config.max_queued_tasks, Some(500));
+    assert_eq!(sologger_config.queue_overflow_policy, QueueOverflowPolicy::Block { timeout_micros: 250 });
+    assert_eq!(sologger_config.drain_timeout_millis, 1000);
+    assert_eq!(sologger_config.config_reload_interval_secs, 10);
+    assert_eq!(sologger_config.block_time_timeout_millis, 0);
+    assert!(sologger_config.emit_orphaned_logs);
+    assert_eq!(
+        sologger_config.slot_status_events,
+        SlotStatusEventsConfig { processed: false, confirmed: true, rooted: true }
+    );
+    assert!(sologger_config.block_program_rollup);
+    assert!(sologger_config.cpi_tree);
+    assert!(sologger_config.traces);
+    assert!(sologger_config.metrics);
+    assert_eq!(sologger_config.metrics_export_interval_secs, 15);
+    assert_eq!(
+        sologger_config.transport(),
+        TransportConfig {
+            stdout: Some(SinkConfig { level: Some("error".to_string()), ..Default::default() }),
+            file: Some(FileTransportConfig {
+                path: "./sologger.ndjson".to_string(),
+                programs: vec!["TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string()],
+                level: None,
+                max_queued_logs: Some(100),
+            }),
+            ..Default::default()
+        }
+    );
+    assert_eq!(
+        sologger_config.accounts_selector.unwrap(),
+        AccountsSelectorConfig { accounts: vec![], owners: vec!["TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string()] }
+    );
+    assert_eq!(
+        sologger_config.transaction_fields,
+        TransactionFieldsConfig { fee: true, compute_units_consumed: true, ..Default::default() }
+    );
+    assert_eq!(sologger_config.idls.get("9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin").unwrap(), "./idls/counter.json");
+    assert_eq!(sologger_config.programs_selector.unwrap().programs, vec!["*"]);
+}
+
+#[test]
+pub fn test_default_transport() {
+    let config = serde_json::from_value::<SologgerConfig>(json!(
+        {
+            "log4rsConfigLocation": "../config/local/log4rs-config.yml",
+            "rpcUrl": "wss://api.devnet.solana.com",
+            "accountDataNotificationsEnabled": false,
+            "transactionNotificationsEnabled": true,
+            "logProcessorWorkerThreadCount": 2
+        }
+    )).unwrap();
+    let sinks: Vec<&str> = config.transport().sinks().into_iter().map(|(name, _)| name).collect();
+    if cfg!(feature = "enable_logstash") {
+        assert_eq!(sinks, vec!["logstash"]);
+    } else if cfg!(feature = "enable_otel") {
+        assert_eq!(sinks, vec!["log4rs", "otel"]);
+    } else {
+        assert!(sinks.is_empty());
+    }
+
+    let config = SologgerConfig { transport: Some(TransportConfig { stdout: Some(SinkConfig::default()), ..Default::default() }), ..config };
+    let sinks: Vec<&str> = config.transport().sinks().into_iter().map(|(name, _)| name).collect();
+    assert_eq!(sinks, vec!["stdout"]);
+}
+
+#[test]
+pub fn test_deserialize_unknown_field() {
+    let config = json!(
+        {
+            "rpcUrl": "wss://api.mainnet-beta.solana.com",
+            "acountDataNotificationsEnabled": false,
+            "transactionNotificationsEnabled": true,
+            "logProcessorWorkerThreadCount": 1
+        }
+    );
+
+    let err = serde_json::from_value::<SologgerConfig>(config).unwrap_err();
+    assert!(err.to_string().starts_with("unknown field `acountDataNotificationsEnabled`"));
+}
+
+#[test]
+pub fn test_deserialize_aliases() {
+    let config = json!(
+        {
+            "rpcUrl": "wss://api.mainnet-beta.solana.com",
+            "programsSelector" : {
+                "programs" : ["9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin"],
+                "aliases" : {
+                    "token": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"
+                }
+            },
+            "accountDataNotificationsEnabled": false,
+            "transactionNotificationsEnabled": true,
+            "logProcessorWorkerThreadCount": 1
+        }
+    );
+
+    let sologger_config = serde_json::from_value::<SologgerConfig>(config).unwrap();
+    let programs_selector = sologger_config.programs_selector.unwrap();
+    assert_eq!(
+        programs_selector.program_ids(),
+        vec!["9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin", "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"]
+    );
+    assert_eq!(
+        programs_selector.program_names().get("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA").unwrap(),
+        "token"
+    );
 }
@@ -0,0 +1,409 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, SendTimeoutError, Sender, TrySendError};
+use log::warn;
+#[cfg(feature = "enable_otel")]
+use opentelemetry::KeyValue;
+use serde_json::json;
+
+use crate::sologger_config::QueueOverflowPolicy;
+
+/// The default maximum number of tasks waiting to be processed by the worker threads
+pub const DEFAULT_MAX_QUEUED_TASKS: usize = 10_000;
+
+/// The minimum time between two log lines reporting dropped tasks
+const DROPPED_TASKS_REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A task that can be prioritized when the queue overflows
+pub trait QueuedTask {
+    /// Returns true if the task carries an error, these are kept in favour of other tasks by [QueueOverflowPolicy::DropNonErrorFirst]
+    fn has_errors(&self) -> bool;
+}
+
+/// A bounded multi-producer, multi-consumer queue of tasks shared between the geyser notifications and the worker threads.
+/// Idle workers block on the queue instead of polling it, and are woken up as soon as a task is pushed.
+/// When the queue is full, the configured [QueueOverflowPolicy] decides which task is dropped.
+pub struct TaskQueue<T> {
+    sender: Sender<T>,
+    receiver: Receiver<T>,
+    overflow_policy: RwLock<QueueOverflowPolicy>,
+    dropped: AtomicU64,
+    dropped_errors: AtomicU64,
+    last_report: Mutex<Option<Instant>>,
+}
+
+impl<T: QueuedTask> TaskQueue<T> {
+    /// Creates a new queue that holds at most `capacity` tasks
+    pub fn new(capacity: usize, overflow_policy: QueueOverflowPolicy) -> Self {
+        let (sender, receiver) = bounded(capacity);
+        TaskQueue {
+            sender,
+            receiver,
+            overflow_policy: RwLock::new(overflow_policy),
+            dropped: AtomicU64::new(0),
+            dropped_errors: AtomicU64::new(0),
+            last_report: Mutex::new(None),
+        }
+    }
+
+    /// Pushes a task onto the queue. If the queue is full, a task is dropped according to the overflow policy.
+    pub fn push(&self, task: T) {
+        // The queue owns a receiver, so it can never be disconnected
+        let task = match self.sender.try_send(task) {
+            Ok(()) | Err(TrySendError::Disconnected(_)) => return,
+            Err(TrySendError::Full(task)) => task,
+        };
+
+        let overflow_policy = *self.overflow_policy.read().unwrap();
+        match overflow_policy {
+            QueueOverflowPolicy::DropNewest => self.record_drop(&task),
+            QueueOverflowPolicy::DropOldest => self.push_dropping_oldest(task),
+            QueueOverflowPolicy::DropNonErrorFirst => {
+                if task.has_errors() {
+                    self.push_dropping_oldest(task);
+                } else {
+                    self.record_drop(&task);
+                }
+            }
+            QueueOverflowPolicy::Block { timeout_micros } => {
+                if let Err(SendTimeoutError::Timeout(task)) = self.sender.send_timeout(task, Duration::from_micros(timeout_micros)) {
+                    self.record_drop(&task);
+                }
+            }
+        }
+    }
+
+    /// Makes room for the task by dropping the oldest queued tasks
+    fn push_dropping_oldest(&self, mut task: T) {
+        loop {
+            if let Ok(oldest) = self.receiver.try_recv() {
+                self.record_drop(&oldest);
+            }
+            match self.sender.try_send(task) {
+                Ok(()) | Err(TrySendError::Disconnected(_)) => return,
+                Err(TrySendError::Full(returned)) => task = returned,
+            }
+        }
+    }
+
+    fn record_drop(&self, task: &T) {
+        let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+        if task.has_errors() {
+            self.dropped_errors.fetch_add(1, Ordering::Relaxed);
+        }
+        #[cfg(feature = "enable_otel")]
+        if let Some(metrics) = crate::otel::program_metrics() {
+            let overflow_policy = self.overflow_policy.read().unwrap().name();
+            metrics.tasks_dropped.add(1, &[KeyValue::new("overflowPolicy", overflow_policy), KeyValue::new("error", task.has_errors())]);
+        }
+
+        let mut last_report = self.last_report.lock().unwrap();
+        let report_due = match *last_report {
+            Some(last_report) => last_report.elapsed() >= DROPPED_TASKS_REPORT_INTERVAL,
+            None => true,
+        };
+        if report_due {
+            *last_report = Some(Instant::now());
+            warn!(target: "sologger_geyser_plugin::task_queue", "{}", json!({
+                "event": "tasksDropped",
+                "dropped": dropped,
+                "droppedErrors": self.dropped_errors(),
+                "overflowPolicy": *self.overflow_policy.read().unwrap(),
+                "capacity": self.capacity(),
+            }));
+        }
+    }
+}
+
+impl<T> TaskQueue<T> {
+    /// Pops the next task, waiting at most `timeout` for one to be pushed
+    pub fn pop_timeout(&self, timeout: Duration) -> Option<T> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(task) => Some(task),
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+
+    /// Changes the overflow policy used for subsequent pushes
+    pub fn set_overflow_policy(&self, overflow_policy: QueueOverflowPolicy) {
+        *self.overflow_policy.write().unwrap() = overflow_policy;
+    }
+
+    /// Returns the number of tasks waiting in the queue
+    pub fn len(&self) -> usize {
+        self.receiver.len()
+    }
+
+    /// Returns true if no tasks are waiting in the queue
+    pub fn is_empty(&self) -> bool {
+        self.receiver.is_empty()
+    }
+
+    /// Returns the maximum number of tasks the queue can hold
+    pub fn capacity(&self) -> usize {
+        self.sender.capacity().unwrap_or_default()
+    }
+
+    /// Returns the total number of tasks dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of dropped tasks that carried an error
+    pub fn dropped_errors(&self) -> u64 {
+        self.dropped_errors.load(Ordering::Relaxed)
+    }
+}
+
+impl<T: QueuedTask> Default for TaskQueue<T> {
+    fn default() -> Self {
+        TaskQueue::new(DEFAULT_MAX_QUEUED_TASKS, QueueOverflowPolicy::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use crossbeam_deque::{Injector, Steal, Worker};
+    use crate::sologger_config::QueueOverflowPolicy;
+    use crate::task_queue::{QueuedTask, TaskQueue};
+
+    /// Negative values are treated as tasks with errors
+    impl QueuedTask for i32 {
+        fn has_errors(&self) -> bool {
+            *self < 0
+        }
+    }
+
+    impl QueuedTask for Instant {
+        fn has_errors(&self) -> bool {
+            false
+        }
+    }
+
+    fn drain(queue: &TaskQueue<i32>) -> Vec<i32> {
+        std::iter::from_fn(|| queue.pop_timeout(Duration::from_millis(1))).collect()
+    }
+
+    #[test]
+    pub fn test_push_pop() {
+        let queue = TaskQueue::new(2, QueueOverflowPolicy::DropNewest);
+        assert_eq!(queue.capacity(), 2);
+        assert!(queue.is_empty());
+
+        queue.push(1);
+        queue.push(2);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.pop_timeout(Duration::from_millis(1)), Some(1));
+        assert_eq!(queue.pop_timeout(Duration::from_millis(1)), Some(2));
+        assert_eq!(queue.pop_timeout(Duration::from_millis(1)), None);
+    }
+
+    #[test]
+    pub fn test_drop_newest() {
+        let queue = TaskQueue::new(2, QueueOverflowPolicy::DropNewest);
+        for task in [1, 2, 3, -4] {
+            queue.push(task);
+        }
+        assert_eq!(drain(&queue), vec![1, 2]);
+        assert_eq!(queue.dropped(), 2);
+        assert_eq!(queue.dropped_errors(), 1);
+    }
+
+    #[test]
+    pub fn test_drop_oldest() {
+        let queue = TaskQueue::new(2, QueueOverflowPolicy::DropOldest);
+        for task in [-1, 2, 3, 4] {
+            queue.push(task);
+        }
+        assert_eq!(drain(&queue), vec![3, 4]);
+        assert_eq!(queue.dropped(), 2);
+        assert_eq!(queue.dropped_errors(), 1);
+    }
+
+    #[test]
+    pub fn test_drop_non_error_first() {
+        let queue = TaskQueue::new(2, QueueOverflowPolicy::DropNonErrorFirst);
+        for task in [1, 2, 3, -4] {
+            queue.push(task);
+        }
+        assert_eq!(drain(&queue), vec![2, -4]);
+        assert_eq!(queue.dropped(), 2);
+        assert_eq!(queue.dropped_errors(), 0);
+    }
+
+    #[test]
+    pub fn test_block() {
+        let queue = Arc::new(TaskQueue::new(1, QueueOverflowPolicy::Block { timeout_micros: 1_000_000 }));
+        queue.push(1);
+
+        let producer_queue = Arc::clone(&queue);
+        let producer = thread::spawn(move || producer_queue.push(2));
+        thread::sleep(Duration::from_millis(20));
+        assert!(!producer.is_finished());
+
+        assert_eq!(queue.pop_timeout(Duration::from_millis(1)), Some(1));
+        producer.join().unwrap();
+        assert_eq!(drain(&queue), vec![2]);
+        assert_eq!(queue.dropped(), 0);
+
+        queue.set_overflow_policy(QueueOverflowPolicy::Block { timeout_micros: 100 });
+        queue.push(3);
+        queue.push(4);
+        assert_eq!(drain(&queue), vec![3]);
+        assert_eq!(queue.dropped(), 1);
+    }
+
+    #[cfg(feature = "enable_otel")]
+    #[test]
+    pub fn test_tasks_dropped_metric() {
+        use opentelemetry_proto::tonic::common::v1::any_value;
+        use opentelemetry_proto::tonic::metrics::v1::{metric::Data, number_data_point};
+        use sologger_log_transport::opentelemetry_config::OpentelemetryConfig;
+        use crate::otel::{tests::{start_collector, GLOBAL_PROVIDERS}, OtelRuntime};
+
+        let _global_providers = GLOBAL_PROVIDERS.lock().unwrap_or_else(|err| err.into_inner());
+        let mut runtime = OtelRuntime::start().unwrap();
+        let (endpoint, collector) = start_collector(runtime.handle());
+        let otel_config = OpentelemetryConfig { metrics_endpoint: endpoint, ..Default::default() };
+        runtime.start_metrics(&otel_config, Duration::from_secs(60)).unwrap();
+
+        // Each failed task replaces the oldest queued one, which also failed
+        let queue = TaskQueue::new(1, QueueOverflowPolicy::DropNonErrorFirst);
+        for task in [-1, -2, -3] {
+            queue.push(task);
+        }
+        assert_eq!(queue.dropped_errors(), 2);
+        // Shutting down the runtime exports the metrics
+        drop(runtime);
+
+        let data_points: Vec<_> = collector.metrics
+            .try_iter()
+            .flat_map(|request| request.resource_metrics)
+            .flat_map(|resource_metrics| resource_metrics.scope_metrics)
+            .flat_map(|scope_metrics| scope_metrics.metrics)
+            .filter(|metric| metric.name == "solana.plugin.tasks_dropped")
+            .flat_map(|metric| match metric.data {
+                Some(Data::Sum(sum)) => sum.data_points,
+                data => panic!("tasks dropped is not a sum: {:?}", data),
+            })
+            .collect();
+        let attribute = |attributes: &[opentelemetry_proto::tonic::common::v1::KeyValue], key: &str| attributes
+            .iter()
+            .find(|attribute| attribute.key == key)
+            .and_then(|attribute| attribute.value.clone()?.value);
+        let dropped_errors = data_points
+            .iter()
+            .find(|data_point| {
+                attribute(&data_point.attributes, "overflowPolicy") == Some(any_value::Value::StringValue("dropNonErrorFirst".to_string()))
+                    && attribute(&data_point.attributes, "error") == Some(any_value::Value::BoolValue(true))
+            })
+            .unwrap();
+        assert_eq!(dropped_errors.value, Some(number_data_point::Value::AsInt(2)));
+    }
+
+    /// The dispatcher used before TaskQueue: an unbounded injector polled by each worker, sleeping 10ms when idle
+    fn run_injector_dispatch(workers: usize, tasks: usize, spacing: Duration) -> (Duration, Vec<Duration>) {
+        let injector: Arc<Injector<Instant>> = Arc::new(Injector::new());
+        let running = Arc::new(AtomicBool::new(true));
+        let handles: Vec<_> = (0..workers).map(|_| {
+            let injector = Arc::clone(&injector);
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                let worker = Worker::<Instant>::new_fifo();
+                let stealer = worker.stealer();
+                let mut latencies = vec![];
+                while running.load(Ordering::SeqCst) || !injector.is_empty() {
+                    if let Some(queued_at) = worker.pop() {
+                        latencies.push(queued_at.elapsed());
+                    } else if let Steal::Success(queued_at) = injector.steal() {
+                        latencies.push(queued_at.elapsed());
+                    } else if let Steal::Success(queued_at) = stealer.steal() {
+                        latencies.push(queued_at.elapsed());
+                    } else {
+                        thread::sleep(Duration::from_millis(10));
+                    }
+                }
+                latencies
+            })
+        }).collect();
+
+        let start = Instant::now();
+        for _ in 0..tasks {
+            injector.push(Instant::now());
+            if !spacing.is_zero() {
+                thread::sleep(spacing);
+            }
+        }
+        running.store(false, Ordering::SeqCst);
+        let latencies = handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect();
+        (start.elapsed(), latencies)
+    }
+
+    fn run_queue_dispatch(workers: usize, tasks: usize, spacing: Duration) -> (Duration, Vec<Duration>) {
+        let queue: Arc<TaskQueue<Instant>> = Arc::new(TaskQueue::new(tasks, QueueOverflowPolicy::DropNewest));
+        let running = Arc::new(AtomicBool::new(true));
+        let handles: Vec<_> = (0..workers).map(|_| {
+            let queue = Arc::clone(&queue);
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                let mut latencies = vec![];
+                while running.load(Ordering::SeqCst) || !queue.is_empty() {
+                    if let Some(queued_at) = queue.pop_timeout(Duration::from_millis(100)) {
+                        latencies.push(queued_at.elapsed());
+                    }
+                }
+                latencies
+            })
+        }).collect();
+
+        let start = Instant::now();
+        for _ in 0..tasks {
+            queue.push(Instant::now());
+            if !spacing.is_zero() {
+                thread::sleep(spacing);
+            }
+        }
+        running.store(false, Ordering::SeqCst);
+        let latencies = handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect();
+        (start.elapsed(), latencies)
+    }
+
+    fn report(name: &str, tasks: usize, (elapsed, mut latencies): (Duration, Vec<Duration>)) -> Duration {
+        assert_eq!(latencies.len(), tasks);
+        latencies.sort();
+        let percentile = |p: f64| latencies[((latencies.len() - 1) as f64 * p) as usize];
+        println!(
+            "{:<24} throughput: {:>10.0} tasks/s  p50: {:>10?}  p99: {:>10?}  max: {:>10?}",
+            name,
+            tasks as f64 / elapsed.as_secs_f64(),
+            percentile(0.5),
+            percentile(0.99),
+            latencies[latencies.len() - 1],
+        );
+        percentile(0.99)
+    }
+
+    /// Compares the throughput and tail latency of TaskQueue against the previous injector loop.
+    /// Run with `cargo test --release bench_dispatch -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    pub fn bench_dispatch() {
+        let workers = 4;
+
+        let tasks = 200_000;
+        report("injector (burst)", tasks, run_injector_dispatch(workers, tasks, Duration::ZERO));
+        report("task queue (burst)", tasks, run_queue_dispatch(workers, tasks, Duration::ZERO));
+
+        let tasks = 500;
+        let spacing = Duration::from_millis(2);
+        let injector_p99 = report("injector (trickle)", tasks, run_injector_dispatch(workers, tasks, spacing));
+        let queue_p99 = report("task queue (trickle)", tasks, run_queue_dispatch(workers, tasks, spacing));
+        assert!(queue_p99 < injector_p99);
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use opentelemetry::trace::{Event, SpanId, Status, TraceContextExt, TraceId, Tracer};
+use opentelemetry::{Context, KeyValue};
+
+use crate::inner_transaction::ReplicaTransactionInfo;
+
+/// The name of the tracer of the transaction traces
+pub const TRACER_NAME: &str = "sologger-geyser-plugin";
+
+/// A program invocation in the logs of a transaction, with the invocations it made through CPI
+#[derive(Debug, Default, PartialEq)]
+struct Invocation {
+    program_id: String,
+    stack_height: u32,
+    compute_units_consumed: Option<u64>,
+    error: Option<String>,
+    /// The `Program log:` messages of the invocation
+    logs: Vec<String>,
+    inner_invocations: Vec<Invocation>,
+}
+
+/// Builds the invocation tree from the `invoke [n]`, `consumed`, `success` and `failed` logs.
+/// Invocations that are still open at the end of the logs, for example because the logs were truncated, are closed there.
+fn invocations(log_messages: &[String]) -> Vec<Invocation> {
+    let mut invocations = Vec::new();
+    let mut stack: Vec<Invocation> = Vec::new();
+    for log in log_messages {
+        let Some(log) = log.strip_prefix("Program ") else {
+            continue;
+        };
+        if let Some(message) = log.strip_prefix("log: ") {
+            if let Some(invocation) = stack.last_mut() {
+                invocation.logs.push(message.to_string());
+            }
+            continue;
+        }
+        let Some((program_id, result)) = log.split_once(' ') else {
+            continue;
+        };
+        if let Some(stack_height) = result.strip_prefix("invoke [").and_then(|result| result.strip_suffix(']')) {
+            stack.push(Invocation {
+                program_id: program_id.to_string(),
+                stack_height: stack_height.parse().unwrap_or_default(),
+                ..Default::default()
+            });
+        } else if let Some(consumed) = result.strip_prefix("consumed ") {
+            if let Some(invocation) = stack.last_mut() {
+                invocation.compute_units_consumed = consumed.split(' ').next().and_then(|units| units.parse().ok());
+            }
+        } else if result == "success" {
+            close_invocation(&mut stack, &mut invocations);
+        } else if let Some(error) = result.strip_prefix("failed: ") {
+            if let Some(invocation) = stack.last_mut() {
+                invocation.error = Some(error.to_string());
+            }
+            close_invocation(&mut stack, &mut invocations);
+        }
+    }
+    while !stack.is_empty() {
+        close_invocation(&mut stack, &mut invocations);
+    }
+    invocations
+}
+
+fn close_invocation(stack: &mut Vec<Invocation>, invocations: &mut Vec<Invocation>) {
+    if let Some(invocation) = stack.pop() {
+        match stack.last_mut() {
+            Some(parent) => parent.inner_invocations.push(invocation),
+            None => invocations.push(invocation),
+        }
+    }
+}
+
+/// Exports the transaction as a trace, with a root span for the transaction and a child span for each program invocation, nested by CPI.
+/// The trace ID is the first 16 bytes of the signature, so the trace of a transaction can be looked up from its signature.
+/// The validator does not report the duration of the invocations, so all spans start and end at the block time of the transaction, or now if it is unknown.
+pub(crate) fn export_trace<T>(tracer: &T, transaction: &ReplicaTransactionInfo, program_names: &HashMap<String, String>)
+where
+    T: Tracer,
+    T::Span: Send + Sync + 'static,
+{
+    let time = transaction.block_time
+        .and_then(|block_time| u64::try_from(block_time).ok())
+        .map_or_else(SystemTime::now, |block_time| UNIX_EPOCH + Duration::from_secs(block_time));
+    let signature = transaction.signature.as_ref();
+    let mut attributes = vec![
+        KeyValue::new("solana.signature", transaction.signature.to_string()),
+        KeyValue::new("solana.slot", transaction.slot as i64),
+    ];
+    if let Some(block_time) = transaction.block_time {
+        attributes.push(KeyValue::new("solana.block_time", block_time));
+    }
+    if transaction.orphaned {
+        attributes.push(KeyValue::new("solana.orphaned", true));
+    }
+    let status = match &transaction.transaction_error {
+        Some(error) => {
+            attributes.push(KeyValue::new("error", error.to_string()));
+            Status::error(error.to_string())
+        }
+        None => Status::Unset,
+    };
+    let span = tracer
+        .span_builder("transaction")
+        .with_trace_id(TraceId::from_bytes(signature[..16].try_into().unwrap()))
+        .with_span_id(SpanId::from_bytes(signature[16..24].try_into().unwrap()))
+        .with_start_time(time)
+        .with_attributes(attributes)
+        .with_status(status)
+        .start_with_context(tracer, &Context::new());
+    let context = Context::new().with_span(span);
+    for invocation in invocations(&transaction.log_messages) {
+        export_invocation(tracer, &context, invocation, time, program_names);
+    }
+    context.span().end_with_timestamp(time);
+}
+
+fn export_invocation<T>(tracer: &T, parent: &Context, invocation: Invocation, time: SystemTime, program_names: &HashMap<String, String>)
+where
+    T: Tracer,
+    T::Span: Send + Sync + 'static,
+{
+    let program_name = program_names.get(&invocation.program_id);
+    let mut attributes = vec![
+        KeyValue::new("solana.program_id", invocation.program_id.clone()),
+        KeyValue::new("solana.stack_height", invocation.stack_height as i64),
+    ];
+    if let Some(program_name) = program_name {
+        attributes.push(KeyValue::new("solana.program_name", program_name.clone()));
+    }
+    if let Some(compute_units_consumed) = invocation.compute_units_consumed {
+        attributes.push(KeyValue::new("solana.compute_units_consumed", compute_units_consumed as i64));
+    }
+    let status = match &invocation.error {
+        Some(error) => {
+            attributes.push(KeyValue::new("error", error.clone()));
+            Status::error(error.clone())
+        }
+        None => Status::Unset,
+    };
+    let events = invocation.logs
+        .into_iter()
+        .map(|message| Event::new("log", time, vec![KeyValue::new("message", message)], 0))
+        .collect();
+    let span = tracer
+        .span_builder(program_name.cloned().unwrap_or_else(|| invocation.program_id.clone()))
+        .with_start_time(time)
+        .with_attributes(attributes)
+        .with_events(events)
+        .with_status(status)
+        .start_with_context(tracer, parent);
+    let context = parent.with_span(span);
+    for inner_invocation in invocation.inner_invocations {
+        export_invocation(tracer, &context, inner_invocation, time, program_names);
+    }
+    context.span().end_with_timestamp(time);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use opentelemetry_proto::tonic::common::v1::any_value::Value;
+    use opentelemetry_proto::tonic::trace::v1::{status::StatusCode, Span};
+    use solana_sdk::signature::Signature;
+    use solana_sdk::transaction::TransactionError;
+    use sologger_log_transport::opentelemetry_config::OpentelemetryConfig;
+    use crate::inner_transaction::ReplicaTransactionInfo;
+    use crate::otel::OtelRuntime;
+    use crate::otel::tests::{start_collector, GLOBAL_PROVIDERS};
+    use crate::transaction_trace::{export_trace, invocations, Invocation, TRACER_NAME};
+
+    fn logs(logs: &[&str]) -> Vec<String> {
+        logs.iter().map(|log| log.to_string()).collect()
+    }
+
+    const SWAP_LOGS: &[&str] = &[
+        "Program 9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7 invoke [1]",
+        "Program log: Instruction: Swap",
+        "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA invoke [2]",
+        "Program log: Instruction: Transfer",
+        "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA consumed 4645 of 180000 compute units",
+        "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA success",
+        "Program 11111111111111111111111111111111 invoke [2]",
+        "Program 11111111111111111111111111111111 failed: custom program error: 0x1",
+        "Program 9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7 consumed 20000 of 200000 compute units",
+        "Program 9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7 failed: custom program error: 0x1",
+    ];
+
+    #[test]
+    fn test_invocations() {
+        assert_eq!(invocations(&logs(SWAP_LOGS)), vec![Invocation {
+            program_id: "9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7".to_string(),
+            stack_height: 1,
+            compute_units_consumed: Some(20000),
+            error: Some("custom program error: 0x1".to_string()),
+            logs: vec!["Instruction: Swap".to_string()],
+            inner_invocations: vec![
+                Invocation {
+                    program_id: "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string(),
+                    stack_height: 2,
+                    compute_units_consumed: Some(4645),
+                    logs: vec!["Instruction: Transfer".to_string()],
+                    ..Default::default()
+                },
+                Invocation {
+                    program_id: "11111111111111111111111111111111".to_string(),
+                    stack_height: 2,
+                    error: Some("custom program error: 0x1".to_string()),
+                    ..Default::default()
+                },
+            ],
+        }]);
+
+        let truncated = invocations(&logs(&SWAP_LOGS[..4]));
+        assert_eq!(truncated.len(), 1);
+        assert_eq!(truncated[0].inner_invocations[0].logs, vec!["Instruction: Transfer"]);
+    }
+
+    fn attribute(span: &Span, key: &str) -> Option<Value> {
+        span.attributes.iter().find(|attribute| attribute.key == key)?.value.clone()?.value
+    }
+
+    #[test]
+    fn test_export_trace() {
+        let _global_providers = GLOBAL_PROVIDERS.lock().unwrap_or_else(|err| err.into_inner());
+        let mut runtime = OtelRuntime::start().unwrap();
+        let (endpoint, collector) = start_collector(runtime.handle());
+        let otel_config = OpentelemetryConfig {
+            log_config: HashMap::from([("service.name".to_string(), "sologger-geyser-plugin".to_string())]),
+            traces_endpoint: endpoint,
+            ..Default::default()
+        };
+        runtime.start_traces(&otel_config).unwrap();
+
+        let signature = Signature::from([7; 64]);
+        let transaction = ReplicaTransactionInfo {
+            signature,
+            log_messages: logs(SWAP_LOGS),
+            transaction_error: Some(TransactionError::AccountNotFound),
+            slot: 2,
+            block_time: Some(1700000000),
+            orphaned: false,
+            fields: None,
+            cpi_tree: None,
+        };
+        let program_names = HashMap::from([("9RX7oz3WN5VRTqekBBHBvEJFVMNRnrCmVy7S6B6S5oU7".to_string(), "swap".to_string())]);
+        export_trace(&opentelemetry::global::tracer(TRACER_NAME), &transaction, &program_names);
+        // Shutting down the runtime exports the remaining spans
+        drop(runtime);
+
+        let spans: Vec<Span> = collector.traces
+            .try_iter()
+            .flat_map(|request| request.resource_spans)
+            .flat_map(|resource_spans| resource_spans.scope_spans)
+            .flat_map(|scope_spans| scope_spans.spans)
+            .collect();
+        let span = |name: &str| spans.iter().find(|span| span.name == name).unwrap();
+        assert_eq!(spans.len(), 4);
+        assert!(spans.iter().all(|span| span.trace_id == signature.as_ref()[..16]));
+
+        let root = span("transaction");
+        assert_eq!(root.span_id, signature.as_ref()[16..24]);
+        assert!(root.parent_span_id.is_empty());
+        assert_eq!(attribute(root, "solana.slot"), Some(Value::IntValue(2)));
+        assert_eq!(root.status.as_ref().unwrap().code, StatusCode::Error as i32);
+
+        let swap = span("swap");
+        assert_eq!(swap.parent_span_id, root.span_id);
+        assert_eq!(attribute(swap, "solana.compute_units_consumed"), Some(Value::IntValue(20000)));
+        assert_eq!(swap.events[0].name, "log");
+        assert_eq!(swap.start_time_unix_nano, 1700000000 * 1_000_000_000);
+
+        let token = span("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+        assert_eq!(token.parent_span_id, swap.span_id);
+        assert_eq!(attribute(token, "solana.stack_height"), Some(Value::IntValue(2)));
+        assert_eq!(token.status.as_ref().map(|status| status.code), Some(StatusCode::Unset as i32));
+
+        let system = span("11111111111111111111111111111111");
+        assert_eq!(system.parent_span_id, swap.span_id);
+        assert_eq!(attribute(system, "error"), Some(Value::StringValue("custom program error: 0x1".to_string())));
+    }
+}